@@ -1,18 +1,23 @@
 use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::str::FromStr;
+
+use rxprog::command::data::{InputFrequency, MultiplicationRatio};
 
 const KV_PAIR_DELIMETER: char = ';';
 const KV_DELIMETER: char = '=';
 
-#[derive(Debug, PartialEq)]
-pub struct ConnectionString<'a> {
-    data: HashMap<&'a str, &'a str>,
+// Keys and values are owned, so a parsed connection string can outlive the
+// string it was parsed from
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConnectionString {
+    data: HashMap<String, String>,
 }
 
-impl<'a> TryFrom<&'a str> for ConnectionString<'a> {
+impl TryFrom<&str> for ConnectionString {
     type Error = &'static str;
 
-    fn try_from(s: &'a str) -> Result<ConnectionString<'a>, &'static str> {
+    fn try_from(s: &str) -> Result<ConnectionString, &'static str> {
         let pairs = s
             .split(KV_PAIR_DELIMETER)
             .map(|kv_pair| {
@@ -47,20 +52,125 @@ impl<'a> TryFrom<&'a str> for ConnectionString<'a> {
         // Check for duplicate keys
         let mut data = HashMap::new();
         for (key, value) in pairs.iter() {
-            if data.contains_key(key) {
+            if data.contains_key(*key) {
                 return Err("duplicate key");
             }
 
-            data.insert(*key, *value);
+            data.insert(key.to_string(), value.to_string());
         }
 
         Ok(ConnectionString { data })
     }
 }
 
-impl ConnectionString<'_> {
+impl ConnectionString {
     pub fn get(&self, key: &str) -> Option<&str> {
-        self.data.get(key).map(|value| *value)
+        self.data.get(key).map(|value| value.as_str())
+    }
+
+    fn get_parsed<T: FromStr>(&self, key: &str) -> Result<Option<T>, String> {
+        self.get(key)
+            .map(|value| {
+                value
+                    .parse::<T>()
+                    .map_err(|_| format!("invalid value \"{}\" for {}", value, key))
+            })
+            .transpose()
+    }
+
+    pub fn get_u8(&self, key: &str) -> Result<Option<u8>, String> {
+        self.get_parsed(key)
+    }
+
+    pub fn get_u32(&self, key: &str) -> Result<Option<u32>, String> {
+        self.get_parsed(key)
+    }
+
+    /// Parses a frequency of the form <value>MHz or <value>Hz, taking a value
+    /// without a unit to be in MHz
+    pub fn get_input_frequency(&self, key: &str) -> Result<Option<InputFrequency>, String> {
+        self.get(key)
+            .map(|value| {
+                let invalid = || {
+                    format!(
+                        "invalid frequency \"{}\" for {} (expected e.g. 12.5MHz or 12500000Hz)",
+                        value, key
+                    )
+                };
+
+                let lowercase = value.to_ascii_lowercase();
+                let frequency = if let Some(mhz) = lowercase.strip_suffix("mhz") {
+                    let mhz = mhz.trim().parse::<f32>().map_err(|_| invalid())?;
+                    InputFrequency::from_mhz(mhz)
+                } else if let Some(hz) = lowercase.strip_suffix("hz") {
+                    let hz = hz.trim().parse::<u32>().map_err(|_| invalid())?;
+                    InputFrequency::from_hz(hz)
+                } else {
+                    let mhz = lowercase.parse::<f32>().map_err(|_| invalid())?;
+                    InputFrequency::from_mhz(mhz)
+                }
+                .map_err(|error| format!("{} for {}", error, key))?;
+
+                if u16::from(frequency) == 0 {
+                    return Err(format!(
+                        "input frequency \"{}\" for {} is out of range",
+                        value, key
+                    ));
+                }
+
+                Ok(frequency)
+            })
+            .transpose()
+    }
+
+    /// Parses a comma separated list of multiplication ratios, each of the
+    /// form x<ratio> or /<ratio>
+    pub fn get_ratios(&self, key: &str) -> Result<Option<Vec<MultiplicationRatio>>, String> {
+        self.get(key)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|mrs| {
+                        let invalid = || {
+                            format!(
+                                "invalid multiplication ratio \"{}\" for {} (expected x<ratio> or /<ratio>)",
+                                mrs, key
+                            )
+                        };
+
+                        // A multiplication ratio must at least be a 'x' or '/'
+                        // followed by one digit, so anything shorter than two
+                        // characters must be invalid. Also stops the
+                        // `split_at()` and `next().unwrap()` calls from
+                        // panicking if the string is too short.
+                        if mrs.len() < 2 || !mrs.is_char_boundary(1) {
+                            return Err(invalid());
+                        }
+
+                        let (c, ratio) = mrs.split_at(1);
+                        let c = c.chars().next().unwrap();
+                        let ratio = ratio.parse::<u8>().map_err(|_| invalid())?;
+
+                        // The protocol encodes division as the negated ratio
+                        // in a signed byte, and has no encoding for zero
+                        if ratio == 0 || ratio > i8::MAX as u8 {
+                            return Err(format!(
+                                "multiplication ratio \"{}\" for {} must be between 1 and {}",
+                                mrs,
+                                key,
+                                i8::MAX
+                            ));
+                        }
+
+                        match c {
+                            'x' => Ok(MultiplicationRatio::MultiplyBy(ratio)),
+                            '/' => Ok(MultiplicationRatio::DivideBy(ratio)),
+                            _ => Err(invalid()),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()
     }
 }
 
@@ -151,4 +261,113 @@ mod tests {
 
         assert_eq!(value, Some("b"));
     }
+
+    #[test]
+    fn get_u32_parses_value() {
+        let cs = ConnectionString::try_from("br=115200").unwrap();
+
+        assert_eq!(cs.get_u32("br"), Ok(Some(115200)));
+    }
+
+    #[test]
+    fn get_input_frequency_missing_key() {
+        let cs = ConnectionString::try_from("a=b").unwrap();
+
+        assert_eq!(cs.get_input_frequency("if"), Ok(None));
+    }
+
+    #[test]
+    fn get_u8_invalid_value() {
+        let cs = ConnectionString::try_from("cm=300").unwrap();
+
+        assert_eq!(
+            cs.get_u8("cm"),
+            Err("invalid value \"300\" for cm".to_string())
+        );
+    }
+
+    #[test]
+    fn get_input_frequency_parses_units() {
+        let cs = ConnectionString::try_from("a=12.5MHz;b=12500000Hz;c=32").unwrap();
+
+        assert_eq!(
+            cs.get_input_frequency("a"),
+            Ok(Some(InputFrequency::from(1250)))
+        );
+        assert_eq!(
+            cs.get_input_frequency("b"),
+            Ok(Some(InputFrequency::from(1250)))
+        );
+        assert_eq!(
+            cs.get_input_frequency("c"),
+            Ok(Some(InputFrequency::from(3200)))
+        );
+    }
+
+    #[test]
+    fn get_input_frequency_out_of_range() {
+        let cs = ConnectionString::try_from("a=1000MHz;b=0Hz;c=fastMHz").unwrap();
+
+        assert_eq!(
+            cs.get_input_frequency("a"),
+            Err("input frequency of 1000 MHz is out of range for a".to_string())
+        );
+        assert_eq!(
+            cs.get_input_frequency("b"),
+            Err("input frequency \"0Hz\" for b is out of range".to_string())
+        );
+        assert_eq!(
+            cs.get_input_frequency("c"),
+            Err(
+                "invalid frequency \"fastMHz\" for c (expected e.g. 12.5MHz or 12500000Hz)"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn get_ratios_parses_values() {
+        let cs = ConnectionString::try_from("mr=x4,/2").unwrap();
+
+        assert_eq!(
+            cs.get_ratios("mr"),
+            Ok(Some(vec![
+                MultiplicationRatio::MultiplyBy(4),
+                MultiplicationRatio::DivideBy(2)
+            ]))
+        );
+    }
+
+    #[test]
+    fn get_ratios_invalid_value() {
+        let cs = ConnectionString::try_from("mr=x4,*2").unwrap();
+
+        assert_eq!(
+            cs.get_ratios("mr"),
+            Err(
+                "invalid multiplication ratio \"*2\" for mr (expected x<ratio> or /<ratio>)"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn get_ratios_rejects_zero() {
+        let cs = ConnectionString::try_from("mr=x4,x0").unwrap();
+
+        assert_eq!(
+            cs.get_ratios("mr"),
+            Err("multiplication ratio \"x0\" for mr must be between 1 and 127".to_string())
+        );
+    }
+
+    #[test]
+    fn outlives_input_string() {
+        let cs = {
+            let s = String::from("a=b");
+            ConnectionString::try_from(s.as_str()).unwrap()
+        };
+
+        assert_eq!(cs.get("a"), Some("b"));
+    }
 }
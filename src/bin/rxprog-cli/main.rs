@@ -1,30 +1,50 @@
 extern crate clap;
 extern crate rxprog;
+extern crate serde_json;
 extern crate serialport;
 
 mod connection_string;
-mod image;
 
 use std::cmp;
 use std::convert::TryFrom;
-use std::ffi::OsStr;
-use std::fmt;
 use std::fs;
+use std::io::{self, Read};
 use std::iter;
+use std::ops::RangeInclusive;
 use std::path::Path;
 use std::time;
 
 use clap::{App, Arg};
-use rxprog::command::data::{MemoryArea, MultiplicationRatio};
+use rxprog::command::data::{DeviceCode, MultiplicationRatio};
+use rxprog::image::{Image, ImageFormat};
 use rxprog::programmer::{
-    Programmer, ProgrammerConnected, ProgrammerConnectedClockModeSelected,
-    ProgrammerConnectedDeviceSelected,
+    ConnectConfig, Programmer, ProgrammerConnected, ProgrammerConnectedClockModeSelected,
+    ProgrammerConnectedDeviceSelected, ProgrammingErasureStateEntry, Timeouts, VerifyMode,
 };
 use rxprog::target::SerialTarget;
+use serde_json::json;
 use serialport::prelude::*;
 
 use connection_string::ConnectionString;
-use image::Image;
+
+// Prints a status message. With --json, standard output is reserved for the
+// JSON query results, so messages are printed to standard error instead.
+macro_rules! status {
+    ($json:expr) => {
+        if $json {
+            eprintln!()
+        } else {
+            println!()
+        }
+    };
+    ($json:expr, $($arg:tt)*) => {
+        if $json {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 fn print_table(headings: Vec<&str>, data: Vec<Vec<&str>>) {
     const COLUMN_SEPARATOR: &str = "    ";
@@ -64,22 +84,85 @@ fn print_table(headings: Vec<&str>, data: Vec<Vec<&str>>) {
     }
 }
 
-fn list_ports() -> Result<(), CLIError> {
+fn print_json(value: &serde_json::Value) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+fn ratio_string(ratio: &MultiplicationRatio) -> String {
+    match ratio {
+        MultiplicationRatio::DivideBy(ratio) => format!("/{}", ratio),
+        MultiplicationRatio::MultiplyBy(ratio) => format!("x{}", ratio),
+    }
+}
+
+fn parse_id_code(s: &str) -> Result<[u8; 16], String> {
+    if s.len() != 32 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("ID code must be 32 hexadecimal digits".to_string());
+    }
+
+    let mut id_code = [0u8; 16];
+    for (i, byte) in id_code.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap();
+    }
+
+    Ok(id_code)
+}
+
+fn ranges_json(ranges: &[RangeInclusive<u32>]) -> serde_json::Value {
+    ranges
+        .iter()
+        .map(|range| json!({ "start": *range.start(), "end": *range.end() }))
+        .collect()
+}
+
+fn list_ports(json: bool) -> Result<(), CLIError> {
     let ports =
-        serialport::available_ports().map_err(|_| "could not retrieve list of available ports")?;
+        rxprog::available_targets().map_err(|_| "could not retrieve list of available ports")?;
+    if json {
+        print_json(&json!({
+            "ports": ports.iter().map(|port| port.port_name.as_str()).collect::<Vec<_>>(),
+        }));
+        return Ok(());
+    }
+
+    if ports.is_empty() {
+        println!("No serial ports found");
+        return Ok(());
+    }
+
     print_table(
-        vec!["Port name"],
+        vec!["Port name", "Product"],
         ports
             .iter()
-            .map(|port| vec![port.port_name.as_str()])
+            .map(|port| {
+                vec![
+                    port.port_name.as_str(),
+                    port.product.as_deref().unwrap_or(""),
+                ]
+            })
             .collect::<Vec<_>>(),
     );
 
     Ok(())
 }
 
-fn list_devices(prog: &mut ProgrammerConnected) -> rxprog::Result<()> {
-    let devices = prog.supported_devices()?;
+fn list_devices(prog: &mut ProgrammerConnected, json: bool) -> rxprog::Result<()> {
+    let devices = prog.unique_supported_devices()?;
+    if json {
+        print_json(&json!({
+            "devices": devices
+                .iter()
+                .map(|device| {
+                    json!({
+                        "device_code": device.device_code.as_str(),
+                        "series_name": device.series_name,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        }));
+        return Ok(());
+    }
+
     print_table(
         vec!["Device code", "Series name"],
         devices
@@ -91,8 +174,16 @@ fn list_devices(prog: &mut ProgrammerConnected) -> rxprog::Result<()> {
     Ok(())
 }
 
-fn list_clock_modes(prog: &mut ProgrammerConnectedDeviceSelected) -> rxprog::Result<()> {
+fn list_clock_modes(
+    prog: &mut ProgrammerConnectedDeviceSelected,
+    json: bool,
+) -> rxprog::Result<()> {
     let clock_modes = prog.clock_modes()?;
+    if json {
+        print_json(&json!({ "clock_modes": clock_modes }));
+        return Ok(());
+    }
+
     let rows = clock_modes
         .iter()
         .map(|clock_mode| vec![clock_mode.to_string()])
@@ -107,6 +198,28 @@ fn list_clock_modes(prog: &mut ProgrammerConnectedDeviceSelected) -> rxprog::Res
     Ok(())
 }
 
+// Prints the multiplication ratios and operating frequencies of each clock as
+// a single JSON object
+fn list_clocks_json(prog: &mut ProgrammerConnectedClockModeSelected) -> rxprog::Result<()> {
+    let multiplication_ratios = prog.multiplication_ratios()?;
+    let operating_frequencies = prog.operating_frequencies()?;
+    let clocks = multiplication_ratios
+        .iter()
+        .zip(&operating_frequencies)
+        .map(|(ratios, operating_frequency)| {
+            json!({
+                "multiplication_ratios": ratios.iter().map(ratio_string).collect::<Vec<_>>(),
+                "minimum_frequency": *operating_frequency.start(),
+                "maximum_frequency": *operating_frequency.end(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    print_json(&json!({ "clocks": clocks }));
+
+    Ok(())
+}
+
 fn list_multiplication_ratios(
     prog: &mut ProgrammerConnectedClockModeSelected,
 ) -> rxprog::Result<()> {
@@ -117,10 +230,7 @@ fn list_multiplication_ratios(
         .map(|(clock, ratios)| {
             let ratios_str = ratios
                 .iter()
-                .map(|ratio| match ratio {
-                    MultiplicationRatio::DivideBy(ratio) => format!("/{}", ratio),
-                    MultiplicationRatio::MultiplyBy(ratio) => format!("x{}", ratio),
-                })
+                .map(ratio_string)
                 .collect::<Vec<_>>()
                 .join(", ");
 
@@ -146,13 +256,13 @@ fn list_operating_frequencies(
         .iter()
         .enumerate()
         .map(|(clock, operating_frequency)| {
-            let min_freq = operating_frequency.start();
-            let max_freq = operating_frequency.end();
+            // Frequencies are reported in MHz * 100
+            let mhz = |frequency: u16| format!("{}.{:02} MHz", frequency / 100, frequency % 100);
 
             vec![
                 clock.to_string(),
-                min_freq.to_string(),
-                max_freq.to_string(),
+                mhz(*operating_frequency.start()),
+                mhz(*operating_frequency.end()),
             ]
         })
         .collect::<Vec<_>>();
@@ -203,48 +313,6 @@ impl From<serialport::Error> for CLIError {
     }
 }
 
-enum ImageType {
-    IHEX,
-    SREC,
-}
-
-impl ImageType {
-    fn from_arg(s: &str) -> ImageType {
-        match s {
-            "ihex" => ImageType::IHEX,
-            "srec" => ImageType::SREC,
-            _ => unreachable!(),
-        }
-    }
-
-    fn from_extension(extension: Option<&OsStr>) -> Option<ImageType> {
-        match extension {
-            Some(extension) => match extension.to_str() {
-                Some(extension) => match extension {
-                    "hex" | "ihex" | "ihx" => Some(ImageType::IHEX),
-                    "srec" | "mot" => Some(ImageType::SREC),
-                    _ => None,
-                },
-                None => None,
-            },
-            None => None,
-        }
-    }
-}
-
-impl fmt::Display for ImageType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                ImageType::IHEX => "ihex",
-                ImageType::SREC => "srec",
-            }
-        )
-    }
-}
-
 fn main2() -> Result<(), CLIError> {
     let matches = App::new("rxprog-cli")
         .arg(
@@ -253,12 +321,42 @@ fn main2() -> Result<(), CLIError> {
                 .short("c")
                 .help("Print the checksums of the user boot and user areas after programming/verifying")
         )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Print the results of queries for the connection string as JSON instead of tables. Other messages are printed to standard error")
+        )
+        .arg(
+            Arg::with_name("no_reset")
+                .long("no-reset")
+                .help("Don't reset the target into boot mode before connecting, for targets which have already been started in boot mode")
+        )
         .arg(
             Arg::with_name("connection_string")
                 .index(1)
                 .help("A semicolon (;) separated list of key=value pairs specifying the required configuration options to connect to a target"),
         )
-        .arg(Arg::with_name("image_path").index(2))
+        .arg(Arg::with_name("image_path").index(2).multiple(true).help("One or more images to program. Images are merged, and must not overlap. Use - to read an image from standard input"))
+        .arg(
+            Arg::with_name("verify")
+                .long("verify")
+                .value_name("VERIFY_MODE")
+                .help("How to verify the image after programming: skip verification, compare the user area checksum, or read back every block")
+                .possible_values(&["none", "checksum", "readback"])
+                .default_value("readback")
+        )
+        .arg(
+            Arg::with_name("verify_only")
+                .long("verify-only")
+                .help("Compare the device's contents with the image without programming it")
+        )
+        .arg(
+            Arg::with_name("id_code")
+                .long("id-code")
+                .value_name("ID_CODE")
+                .help("The target's ID code as 32 hexadecimal digits, for targets with ID code protection enabled")
+                .validator(|s| parse_id_code(&s).map(|_| ()))
+        )
         .arg(Arg::with_name("image_type").long("image-type").short("T").value_name("IMAGE_TYPE").help("The type of the image file").possible_values(&["ihex", "srec"]).takes_value(true))
         .long_about("Programming utility for Renesas microcontrollers supporting the Boot Mode protocol\n\
 \n\
@@ -273,9 +371,11 @@ Each query shows the fields of the connection string which need to be populated
 For example, to connect to a target on COM3 to query the available devices:\n\
 \trxprog-cli \"p=COM3\"\n\
 To connect to a target on /dev/ttyS4 and program an image:\n\
-\trxprog-cli \"p=/dev/ttyS4;d=7805;cm=0;if=3200;mr=x1,x1;br=115200\" image.ihex\n\
+\trxprog-cli \"p=/dev/ttyS4;d=7805;cm=0;if=32MHz;mr=x1,x1;br=115200\" image.ihex\n\
+\n\
+rxprog-cli will attempt to guess the format of the image based on its extension. If the image has a non-standard extension, the image type can be specified explicitly with -T.\n\
 \n\
-rxprog-cli will attempt to guess the format of the image based on its extension. If the image has a non-standard extension, the image type can be specified explicitly with -T.\n")
+An image can be read from standard input by specifying - as its path. Since standard input is then unavailable for reset prompts, this requires -T and --no-reset.\n")
         .about("Programming utility for Renesas microcontrollers supporting the Boot Mode protocol")
         .get_matches();
 
@@ -287,18 +387,46 @@ rxprog-cli will attempt to guess the format of the image based on its extension.
     let connection_string = ConnectionString::try_from(connection_string)
         .map_err(|e| format!("could not parse connection string ({})", e))?;
 
+    let json = matches.is_present("json");
+
     let port = connection_string.get("p");
     if port.is_none() {
-        println!("No port specified in connection string. Listing available serial ports:");
-        list_ports()?;
+        status!(
+            json,
+            "No port specified in connection string. Listing available serial ports:"
+        );
+        list_ports(json)?;
 
-        println!();
-        println!("Hint: select a port with p=<port name>");
+        status!(json);
+        status!(json, "Hint: select a port with p=<port name>");
         return Ok(());
     }
     let port = port.unwrap();
 
-    println!("Connecting to target on {}", port);
+    // Standard input is read up front, as the target's reset prompt also
+    // reads from it
+    let reads_stdin = matches
+        .values_of("image_path")
+        .into_iter()
+        .flatten()
+        .any(|image_path| image_path == "-");
+    let stdin_image = if reads_stdin {
+        if !matches.is_present("image_type") {
+            return Err("reading an image from standard input requires -T".into());
+        }
+
+        if !matches.is_present("no_reset") {
+            return Err("reading an image from standard input requires --no-reset".into());
+        }
+
+        let mut image_string = String::new();
+        io::stdin().read_to_string(&mut image_string)?;
+        Some(image_string)
+    } else {
+        None
+    };
+
+    status!(json, "Connecting to target on {}", port);
 
     let p = serialport::open_with_settings(
         port,
@@ -312,182 +440,238 @@ rxprog-cli will attempt to guess the format of the image based on its extension.
         },
     )?;
     let target = SerialTarget::new(p);
-    let mut prog = Programmer::new(Box::new(target)).connect()?;
-
-    println!("Initial connection succeeded");
-
-    let device = connection_string.get("d");
-    if device.is_none() {
-        println!();
-        println!(
-            "No device specified in connection string. Querying target for supported devices:"
-        );
-        list_devices(&mut prog)?;
-
-        println!();
-        println!("Hint: select a device with d=<device code>");
-        return Ok(());
-    }
-    let device = device.unwrap();
-
-    let mut prog = prog.select_device(&device.to_string())?;
-
-    let clock_mode = connection_string.get("cm");
-    if clock_mode.is_none() {
-        println!();
-        println!("No clock mode specified in connection string. Querying target for supported clock modes:");
-        list_clock_modes(&mut prog)?;
-
-        println!();
-        println!("Hint: select a clock mode with cm=<clock mode>");
-        return Ok(());
-    }
-    let clock_mode = clock_mode
-        .unwrap()
-        .parse::<u8>()
-        .map_err(|_| "invalid clock mode")?;
+    let config = ConnectConfig {
+        skip_reset: matches.is_present("no_reset"),
+        timeouts: Some(Timeouts {
+            command: time::Duration::from_millis(1_000),
+            long_operation: time::Duration::from_millis(30_000),
+        }),
+        ..ConnectConfig::default()
+    };
+    let mut prog = Programmer::with_config(Box::new(target), config).connect()?;
+
+    status!(json, "Initial connection succeeded");
+
+    let device = match connection_string.get("d") {
+        Some(device) => DeviceCode::new(device)?,
+        None => match prog.only_supported_device()? {
+            Some(device) => {
+                status!(
+                    json,
+                    "No device specified in connection string. Selecting the only supported device, {} ({})",
+                    device.device_code, device.series_name
+                );
+                device.device_code
+            }
+            None => {
+                status!(json);
+                status!(
+                    json,
+                    "No device specified in connection string. Querying target for supported devices:"
+                );
+                list_devices(&mut prog, json)?;
+
+                status!(json);
+                status!(json, "Hint: select a device with d=<device code>");
+                return Ok(());
+            }
+        },
+    };
+
+    let mut prog = prog.select_device(&device)?;
+
+    let clock_mode = match connection_string.get_u8("cm")? {
+        Some(clock_mode) => clock_mode,
+        None => match prog.only_clock_mode()? {
+            Some(clock_mode) => {
+                status!(
+                    json,
+                    "No clock mode specified in connection string. Selecting the only supported clock mode, {}",
+                    clock_mode
+                );
+                clock_mode
+            }
+            None => {
+                status!(json);
+                status!(json, "No clock mode specified in connection string. Querying target for supported clock modes:");
+                list_clock_modes(&mut prog, json)?;
+
+                status!(json);
+                status!(json, "Hint: select a clock mode with cm=<clock mode>");
+                return Ok(());
+            }
+        },
+    };
 
     let mut prog = prog.select_clock_mode(clock_mode)?;
 
-    let bit_rate = connection_string.get("br");
-    let input_frequency = connection_string.get("if");
-    let multiplication_ratios = connection_string.get("mr");
+    let bit_rate = connection_string.get_u32("br")?;
+    let input_frequency = connection_string.get_input_frequency("if")?;
+    let multiplication_ratios = connection_string.get_ratios("mr")?;
     if bit_rate.is_none() || input_frequency.is_none() || multiplication_ratios.is_none() {
-        println!();
-        println!("No input frequency, multiplication ratio and/or bit rate specified in connection string. Querying target for supported multiplication ratios and operating frequency ranges:");
-        list_multiplication_ratios(&mut prog)?;
-        list_operating_frequencies(&mut prog)?;
+        status!(json);
+        status!(json, "No input frequency, multiplication ratio and/or bit rate specified in connection string. Querying target for supported multiplication ratios and operating frequency ranges:");
+        if json {
+            list_clocks_json(&mut prog)?;
+        } else {
+            list_multiplication_ratios(&mut prog)?;
+            list_operating_frequencies(&mut prog)?;
+        }
 
-        println!();
-        println!("Hint: select an input frequency, multiplication ratio and bit rate with if=<input frequency>;mr=<ratio 1>,<ratio 2>,...;br=<bit rate>");
+        status!(json);
+        status!(json, "Hint: select an input frequency, multiplication ratio and bit rate with if=<input frequency in MHz>;mr=<ratio 1>,<ratio 2>,...;br=<bit rate>");
         return Ok(());
     }
-    let bit_rate = bit_rate
-        .unwrap()
-        .parse::<u32>()
-        .map_err(|_| "invalid bit rate")?;
+    let bit_rate = bit_rate.unwrap();
     if bit_rate % 100 != 0 {
         return Err("bit rate must be a multiple of 100".into());
     }
-    let input_frequency = input_frequency
-        .unwrap()
-        .parse::<u16>()
-        .map_err(|_| "invalid input frequency")?;
-    let multiplication_ratios = multiplication_ratios
-        .unwrap()
-        .split(',')
-        .map(|mrs| {
-            // A multiplication ratio must at least be a 'x' or '/' followed by
-            // one digit, so anything shorter than two characters must be
-            // invalid. Also stops the `split_at()` and `next().unwrap()` calls
-            // from panicking if the string is too short.
-            if mrs.len() < 2 {
-                return Err(());
-            }
-
-            let (c, ratio) = mrs.split_at(1);
-            let c = c.chars().next().unwrap();
-            let ratio = ratio.parse::<u8>().map_err(|_| ())?;
-
-            match c {
-                'x' => Ok(MultiplicationRatio::MultiplyBy(ratio)),
-                '/' => Ok(MultiplicationRatio::DivideBy(ratio)),
-                _ => Err(()),
-            }
-        })
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|_| "invalid multiplication ratio")?;
+    let bit_rate = u16::try_from(bit_rate / 100)
+        .map_err(|_| format!("bit rate of {} is too high", bit_rate))?;
+    let input_frequency = input_frequency.unwrap();
+    let multiplication_ratios = multiplication_ratios.unwrap();
 
-    let bit_rate = (bit_rate / 100) as u16;
     let mut prog = prog.set_new_bit_rate(bit_rate, input_frequency, multiplication_ratios)?;
 
-    let image_path = matches.value_of("image_path");
-    if image_path.is_none() {
+    let image_paths = matches.values_of("image_path");
+    if image_paths.is_none() && json {
+        print_json(&json!({
+            "user_boot_area": ranges_json(&prog.user_boot_area()?),
+            "user_area": ranges_json(&prog.user_area()?),
+            "erasure_blocks": ranges_json(&prog.erasure_block()?),
+        }));
+        return Ok(());
+    }
+    if image_paths.is_none() {
         println!();
         println!("Hint: specify an image to program the device");
         println!("Nothing to do");
         return Ok(());
     }
-    let image_path = image_path.unwrap();
-    let image_string = fs::read_to_string(image_path)?;
-
-    let image_type = matches
-        .value_of("image_type")
-        .map(ImageType::from_arg)
-        .or_else(|| {
-            let image_type = ImageType::from_extension(Path::new(image_path).extension());
-
-            // If we guessed the type of the image from the extension, tell the
-            // user. We could totally be wrong!
-            if let Some(image_type) = &image_type {
-                println!("Detected {} image from extension", image_type);
-            }
-
-            image_type
-        })
-        .ok_or("could not determine image type (hint: specify explicitly with -T)")?;
-
-    let mut image = Image::new(&prog.user_area()?);
-    match image_type {
-        ImageType::IHEX => {
-            let reader = ihex::Reader::new(image_string.as_str());
-            image
-                .add_data_from_ihex(reader)
-                .map_err(|e| format!("failed to parse ihex ({})", e))?;
-        }
-        ImageType::SREC => {
-            let records = srec::read_records(image_string.as_str());
-            image
-                .add_data_from_srec(records)
-                .map_err(|e| format!("failed to parse srec ({})", e))?;
+    let image_paths = image_paths.unwrap();
+
+    let user_area = prog.user_area()?;
+    let mut image = Image::new(&user_area);
+    for image_path in image_paths {
+        let image_string = match (image_path, &stdin_image) {
+            ("-", Some(stdin_image)) => stdin_image.clone(),
+            _ => fs::read_to_string(image_path)?,
+        };
+
+        let image_type = matches
+            .value_of("image_type")
+            .map(|image_type| match image_type {
+                "ihex" => ImageFormat::Ihex,
+                "srec" => ImageFormat::Srec,
+                _ => unreachable!(),
+            })
+            .or_else(|| {
+                let image_type = ImageFormat::from_path(Path::new(image_path));
+
+                // If we guessed the type of the image from the extension, tell the
+                // user. We could totally be wrong!
+                if let Some(image_type) = &image_type {
+                    println!(
+                        "Detected {} image from extension of {}",
+                        image_type, image_path
+                    );
+                }
+
+                image_type
+            })
+            .ok_or("could not determine image type (hint: specify explicitly with -T)")?;
+
+        let mut file_image = Image::new(&user_area);
+        let uncovered = file_image
+            .add_data_from_str(&image_string, image_type)
+            .map_err(|e| e.description)?;
+        for range in uncovered {
+            println!(
+                "Warning: skipping data in {} outside of the user area at {:#X}-{:#X}",
+                image_path,
+                range.start(),
+                range.end()
+            );
         }
+
+        image.merge(&file_image).map_err(|address| {
+            format!(
+                "{} overlaps a previously loaded image at {:#X}",
+                image_path, address
+            )
+        })?;
     }
 
-    let prog = prog.programming_erasure_state_transition()?;
+    println!();
+    println!("Memory map:");
+    print!("{}", image.memory_map_string());
+    println!();
+
+    let mut prog = match prog.programming_erasure_state_transition()? {
+        ProgrammingErasureStateEntry::Entered(prog) => prog,
+        ProgrammingErasureStateEntry::IDCodeRequired(prog) => match matches.value_of("id_code") {
+            Some(id_code) => prog.submit_id_code(&parse_id_code(id_code)?)?,
+            None => {
+                return Err(
+                    "Target has ID code protection enabled, specify its ID code with --id-code"
+                        .into(),
+                )
+            }
+        },
+    };
 
     println!("Transitioned to programming/erasure state successfully");
     println!();
 
-    println!("Programming...");
-    let mut prog = prog.program_user_or_data_area()?;
-    for block in image.programmable_blocks(256) {
-        let mut data = [0u8; 256];
-        data.copy_from_slice(&block.data);
-        prog.program_block(block.start_address, data)?;
+    let verify_mode = match matches.value_of("verify").unwrap() {
+        "none" => VerifyMode::None,
+        "checksum" => VerifyMode::Checksum,
+        "readback" => VerifyMode::FullReadback,
+        _ => unreachable!(),
+    };
+
+    let verify_only = matches.is_present("verify_only");
+    if verify_only && verify_mode == VerifyMode::None {
+        return Err("--verify-only requires a verification mode other than none".into());
     }
-    let mut prog = prog.end()?;
-    println!("Programming complete.");
 
-    println!("Verifying...");
-    let mut verification_failed = false;
-    for block in image.programmable_blocks(256) {
-        let programmed_data = prog.read_memory(
-            MemoryArea::UserArea,
-            block.start_address,
-            block.data.len() as u32,
-        )?;
+    let mut prog = if verify_only {
+        prog
+    } else {
+        println!("Programming...");
+        prog.program_image(&image, VerifyMode::None)?;
+        println!("Programming complete.");
+
+        prog
+    };
+
+    if verify_mode != VerifyMode::None {
+        println!("Verifying...");
+        let report = prog.verify_image(&image, verify_mode)?;
 
-        if programmed_data != block.data {
-            verification_failed = true;
+        for address in &report.mismatched_blocks {
+            println!("Verify: block at {:#X} did not match", address);
+        }
 
+        if let Some(checksums) = report.checksums {
             println!(
-                "Verify: block of {:#X} bytes at {:#X} did not match",
-                block.data.len(),
-                block.start_address
+                "Verify: image checksum {:#010X}, device checksum {:#010X}",
+                checksums.image, checksums.device
             );
         }
-    }
 
-    if !verification_failed {
-        println!("Verification complete.");
-    } else {
-        println!("Verification failed.");
+        if report.verified {
+            println!("Verification complete.");
+        } else {
+            println!("Verification failed.");
+        }
     }
 
-    if matches.is_present("show_checksums") {
-        let uba_checksum = prog.user_boot_area_checksum()?;
-        let ua_checksum = prog.user_area_checksum()?;
+    let summary = prog.finish(matches.is_present("show_checksums"))?;
 
+    if let (Some(uba_checksum), Some(ua_checksum)) =
+        (summary.user_boot_area_checksum, summary.user_area_checksum)
+    {
         println!();
         println!("User boot area checksum: {:#010X}", uba_checksum);
         println!("User area checksum: {:#010X}", ua_checksum);
@@ -1,16 +1,90 @@
+use std::any;
 use std::fmt;
 use std::io;
 use std::num::Wrapping;
+use std::time;
 
-use crate::Result;
+use crate::target::Target;
+use crate::{Error, ErrorKind, Result};
 
 /// A command which can be sent to a device, and results in either a response or error
+///
+/// All errors are reported through the crate's `Result`, so commands have no error type of their
+/// own. Commands within the crate don't implement this trait directly: each implements
+/// `TransmitCommandData` to describe the bytes it sends and `Receive` to parse its response, and
+/// gets `Command` from the blanket implementation, which also annotates timeouts with the
+/// command's name.
 pub trait Command {
     /// Result of a successful command execution
     type Response;
 
     /// Executes the command on a device
     fn execute<T: io::Read + io::Write>(&self, p: &mut T) -> Result<Self::Response>;
+
+    /// Executes the command on a device, also returning the raw bytes of the device's response,
+    /// e.g. to compare against the datasheet when a response is parsed unexpectedly. To see the
+    /// bytes of responses which fail to parse, wrap the target in a `TeeTarget` instead.
+    fn execute_raw<T: io::Read + io::Write>(&self, p: &mut T) -> Result<(Self::Response, Vec<u8>)> {
+        let mut recorder = Recorder {
+            inner: p,
+            data: vec![],
+        };
+        let response = self.execute(&mut recorder)?;
+
+        Ok((response, recorder.data))
+    }
+
+    /// Executes the command on a device with the target's read timeout set to `timeout`, e.g. to
+    /// allow a slow operation longer to respond. The previous timeout is restored afterwards if
+    /// the target reports it. If `timeout` is `None`, the target's timeout is left unchanged.
+    /// Failing to restore the timeout is only reported if the command itself succeeded.
+    fn execute_with_timeout<T: Target + ?Sized>(
+        &self,
+        target: &mut T,
+        timeout: Option<time::Duration>,
+    ) -> Result<Self::Response> {
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return self.execute(&mut &mut *target),
+        };
+
+        let previous_timeout = target.timeout();
+        target.set_timeout(timeout)?;
+        let result = self.execute(&mut &mut *target);
+        if let Some(previous_timeout) = previous_timeout {
+            let restored = target.set_timeout(previous_timeout);
+            if result.is_ok() {
+                restored?;
+            }
+        }
+
+        result
+    }
+}
+
+// Records every byte read through it, passing writes straight through
+struct Recorder<'a, T: io::Read + io::Write> {
+    inner: &'a mut T,
+    data: Vec<u8>,
+}
+
+impl<T: io::Read + io::Write> io::Read for Recorder<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = self.inner.read(buf)?;
+        self.data.extend(&buf[..length]);
+
+        Ok(length)
+    }
+}
+
+impl<T: io::Read + io::Write> io::Write for Recorder<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 pub trait Transmit {
@@ -47,6 +121,7 @@ impl CommandData {
     }
 }
 
+/// Describes the bytes sent for a command. Implement along with `Receive` to implement `Command`.
 pub trait TransmitCommandData {
     fn command_data(&self) -> CommandData;
 }
@@ -60,18 +135,35 @@ impl<T: TransmitCommandData> Transmit for T {
     }
 }
 
+/// Parses the response to a command. Implement along with `TransmitCommandData` to implement
+/// `Command`.
 pub trait Receive {
     type Response;
 
     fn rx<T: io::Read>(&self, p: &mut T) -> Result<Self::Response>;
 }
 
-impl<T: Transmit + Receive> Command for T {
+impl<T: TransmitCommandData + Receive> Command for T {
     type Response = T::Response;
 
     fn execute<U: io::Read + io::Write>(&self, p: &mut U) -> Result<Self::Response> {
         self.tx(p)?;
-        self.rx(p)
+        self.rx(p).map_err(|error| match error.kind {
+            ErrorKind::Io(io::ErrorKind::TimedOut) => {
+                // Strip the module path, leaving just the command's name
+                let name = any::type_name::<T>().rsplit("::").next().unwrap();
+                let opcode = self.command_data().opcode;
+
+                Error::new(
+                    error.kind,
+                    format!(
+                        "timed out waiting for response to {} ({:#04X})",
+                        name, opcode
+                    ),
+                )
+            }
+            _ => error,
+        })
     }
 }
 
@@ -136,3 +228,163 @@ impl fmt::Display for CommandError {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TimingOut {}
+
+    impl io::Read for TimingOut {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::TimedOut.into())
+        }
+    }
+
+    impl io::Write for TimingOut {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Responds to a ClockModeInquiry, recording the timeout in effect when the
+    // command was sent
+    struct TimeoutTarget {
+        timeout: time::Duration,
+        write_timeout: Option<time::Duration>,
+        response: io::Cursor<Vec<u8>>,
+        fail_restore: bool,
+    }
+
+    impl Target for TimeoutTarget {
+        fn clear_buffers(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_baud_rate(&mut self, _baud_rate: u32) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn bytes_to_read(&mut self) -> io::Result<u32> {
+            Ok(0)
+        }
+
+        fn reset_into(&mut self, _operating_mode: crate::target::OperatingMode) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_timeout(&mut self, timeout: time::Duration) -> io::Result<()> {
+            if self.fail_restore && self.write_timeout.is_some() {
+                return Err(io::Error::new(io::ErrorKind::Other, "restore failed"));
+            }
+
+            self.timeout = timeout;
+            Ok(())
+        }
+
+        fn timeout(&self) -> Option<time::Duration> {
+            Some(self.timeout)
+        }
+    }
+
+    impl io::Read for TimeoutTarget {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.response.read(buf)
+        }
+    }
+
+    impl io::Write for TimeoutTarget {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.write_timeout = Some(self.timeout);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn execute_with_timeout_restores_timeout() {
+        let cmd = super::super::commands::ClockModeInquiry {};
+        let mut target = TimeoutTarget {
+            timeout: time::Duration::from_secs(1),
+            write_timeout: None,
+            response: io::Cursor::new(vec![0x31, 0x02, 0x00, 0x01, 0xCC]),
+            fail_restore: false,
+        };
+
+        let response = cmd.execute_with_timeout(&mut target, Some(time::Duration::from_secs(30)));
+
+        assert_eq!(response, Ok(vec![0x00, 0x01]));
+        assert_eq!(target.write_timeout, Some(time::Duration::from_secs(30)));
+        assert_eq!(target.timeout, time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn execute_with_timeout_reports_restore_failure_after_success() {
+        let cmd = super::super::commands::ClockModeInquiry {};
+        let mut target = TimeoutTarget {
+            timeout: time::Duration::from_secs(1),
+            write_timeout: None,
+            response: io::Cursor::new(vec![0x31, 0x02, 0x00, 0x01, 0xCC]),
+            fail_restore: true,
+        };
+
+        let response = cmd.execute_with_timeout(&mut target, Some(time::Duration::from_secs(30)));
+
+        assert_eq!(
+            response.map_err(|e| e.kind),
+            Err(ErrorKind::Io(io::ErrorKind::Other))
+        );
+    }
+
+    #[test]
+    fn execute_with_timeout_keeps_command_error_when_restore_fails() {
+        let cmd = super::super::commands::ClockModeInquiry {};
+        let mut target = TimeoutTarget {
+            timeout: time::Duration::from_secs(1),
+            write_timeout: None,
+            response: io::Cursor::new(vec![0x00]),
+            fail_restore: true,
+        };
+
+        let response = cmd.execute_with_timeout(&mut target, Some(time::Duration::from_secs(30)));
+
+        assert_eq!(response.map_err(|e| e.kind), Err(ErrorKind::Protocol));
+    }
+
+    #[test]
+    fn execute_raw_returns_response_bytes() {
+        let cmd = super::super::commands::ClockModeInquiry {};
+        let response_bytes = [0x31, 0x02, 0x00, 0x01, 0xCC];
+        let mut p = mock_io::Builder::new()
+            .write(&[0x21])
+            .read(&response_bytes)
+            .build();
+
+        let response = cmd.execute_raw(&mut p);
+
+        assert_eq!(response, Ok((vec![0x00, 0x01], response_bytes.to_vec())));
+    }
+
+    #[test]
+    fn execute_annotates_timeout() {
+        let cmd = super::super::commands::ClockModeInquiry {};
+        let mut p = TimingOut {};
+
+        let response = cmd.execute(&mut p);
+
+        assert_eq!(
+            response,
+            Err(Error::new(
+                ErrorKind::Io(io::ErrorKind::TimedOut),
+                "timed out waiting for response to ClockModeInquiry (0x21)"
+            ))
+        );
+    }
+}
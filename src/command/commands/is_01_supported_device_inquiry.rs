@@ -1,6 +1,7 @@
 use std::str;
 
 use super::command_impl_prelude::*;
+use crate::{Error, ErrorKind};
 
 /// Request a list of devices supported by the boot program
 #[derive(Debug)]
@@ -36,13 +37,26 @@ impl Receive for SupportedDeviceInquiry {
 
             let (device_code_bytes, series_name_bytes) = device_bytes.split_at(4);
 
+            let device_code = str::from_utf8(device_code_bytes)
+                .ok()
+                .and_then(|device_code| DeviceCode::new(device_code).ok())
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::Protocol,
+                        format!("invalid device code {:02X?}", device_code_bytes),
+                    )
+                })?;
+
+            let series_name = str::from_utf8(series_name_bytes).map_err(|_| {
+                Error::new(
+                    ErrorKind::Protocol,
+                    format!("invalid series name {:02X?}", series_name_bytes),
+                )
+            })?;
+
             devices.push(SupportedDevice {
-                device_code: str::from_utf8(device_code_bytes)
-                    .expect("Could not decode device code")
-                    .to_string(),
-                series_name: str::from_utf8(series_name_bytes)
-                    .expect("Could not decode series name")
-                    .to_string(),
+                device_code,
+                series_name: series_name.to_string(),
             });
 
             remaining_data = &remaining_data[(1 + character_count)..];
@@ -87,15 +101,53 @@ mod tests {
             response,
             Ok(vec![
                 SupportedDevice {
-                    device_code: "DEV1".to_string(),
+                    device_code: DeviceCode::new("DEV1").unwrap(),
                     series_name: "ABCD".to_string(),
                 },
                 SupportedDevice {
-                    device_code: "DEV2".to_string(),
+                    device_code: DeviceCode::new("DEV2").unwrap(),
                     series_name: "VWXYZ".to_string(),
                 },
             ])
         );
         assert!(is_script_complete(&mut p));
     }
+
+    #[test]
+    fn test_rx_invalid_device_code() {
+        let cmd = SupportedDeviceInquiry {};
+        let response_bytes = [
+            0x30, 0x06, 0x01, // Header
+            0x04, 0xC3, 0xA9, 0x41, 0x42, // Device 1
+            0xD6, // Checksum
+        ];
+        let mut p = mock_io::Builder::new().read(&response_bytes).build();
+
+        let response = cmd.rx(&mut p);
+
+        assert_eq!(response.map_err(|e| e.kind), Err(ErrorKind::Protocol));
+        assert!(is_script_complete(&mut p));
+    }
+
+    #[test]
+    fn test_rx_invalid_series_name() {
+        let cmd = SupportedDeviceInquiry {};
+        let response_bytes = [
+            0x30, 0x08, 0x01, // Header
+            0x06, 0x44, 0x45, 0x56, 0x31, 0xFF, 0xFE, // Device 1
+            0xB4, // Checksum
+        ];
+        let mut p = mock_io::Builder::new().read(&response_bytes).build();
+
+        let response = cmd.rx(&mut p);
+
+        assert_eq!(
+            response,
+            Err(Error::new(
+                ErrorKind::Protocol,
+                "invalid series name [FF, FE]"
+            ))
+        );
+        assert!(is_script_complete(&mut p));
+    }
 }
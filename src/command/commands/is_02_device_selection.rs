@@ -3,18 +3,16 @@ use super::command_impl_prelude::*;
 /// Select a device
 #[derive(Debug)]
 pub struct DeviceSelection {
-    /// The 4 character device code of the device to select
-    pub device_code: String,
+    /// The device code of the device to select
+    pub device_code: DeviceCode,
 }
 
 impl TransmitCommandData for DeviceSelection {
     fn command_data(&self) -> CommandData {
-        assert_eq!(self.device_code.len(), 4);
-
         CommandData {
             opcode: 0x10,
             has_size_field: true,
-            payload: self.device_code.bytes().collect(),
+            payload: self.device_code.as_str().bytes().collect(),
         }
     }
 }
@@ -48,7 +46,7 @@ mod tests {
     #[test]
     fn test_tx() -> Result<()> {
         let cmd = DeviceSelection {
-            device_code: "DEV1".to_string(),
+            device_code: DeviceCode::new("DEV1").unwrap(),
         };
         let command_bytes = [0x10, 0x04, 0x44, 0x45, 0x56, 0x31, 0xDC];
         let mut p = mock_io::Builder::new().write(&command_bytes).build();
@@ -63,7 +61,7 @@ mod tests {
     #[test]
     fn test_rx_success() {
         let cmd = DeviceSelection {
-            device_code: "DEV1".to_string(),
+            device_code: DeviceCode::new("DEV1").unwrap(),
         };
         let response_bytes = [0x06];
         let mut p = mock_io::Builder::new().read(&response_bytes).build();
@@ -77,7 +75,7 @@ mod tests {
     #[test]
     fn test_rx_fail() {
         let cmd = DeviceSelection {
-            device_code: "DEV1".to_string(),
+            device_code: DeviceCode::new("DEV1").unwrap(),
         };
         let response_bytes = [0x90, 0x21];
         let mut p = mock_io::Builder::new().read(&response_bytes).build();
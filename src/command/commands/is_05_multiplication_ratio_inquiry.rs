@@ -1,3 +1,5 @@
+use std::convert::TryFrom;
+
 use super::command_impl_prelude::*;
 
 /// Request a list of supported multiplication ratios for each clock
@@ -36,8 +38,8 @@ impl Receive for MultiplicationRatioInquiry {
             clock_types.push(
                 multiplication_ratios
                     .iter()
-                    .map(|x| MultiplicationRatio::from(*x))
-                    .collect(),
+                    .map(|x| MultiplicationRatio::try_from(*x))
+                    .collect::<Result<_>>()?,
             );
 
             remaining_data = &remaining_data[(1 + multiplication_ratio_count)..];
@@ -51,6 +53,7 @@ impl Receive for MultiplicationRatioInquiry {
 mod tests {
     use super::super::test_util::is_script_complete;
     use super::*;
+    use crate::ErrorKind;
 
     #[test]
     fn test_tx() -> Result<()> {
@@ -99,4 +102,20 @@ mod tests {
         );
         assert!(is_script_complete(&mut p));
     }
+
+    #[test]
+    fn test_rx_zero_ratio() {
+        let cmd = MultiplicationRatioInquiry {};
+        let response_bytes = [
+            0x32, 0x04, 0x01, // Header
+            0x02, 0x01, 0x00, // Clock type 1
+            0xC6, // Checksum
+        ];
+        let mut p = mock_io::Builder::new().read(&response_bytes).build();
+
+        let response = cmd.rx(&mut p);
+
+        assert_eq!(response.map_err(|e| e.kind), Err(ErrorKind::Protocol));
+        assert!(is_script_complete(&mut p));
+    }
 }
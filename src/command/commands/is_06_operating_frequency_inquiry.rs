@@ -4,7 +4,11 @@ use super::command_impl_prelude::*;
 
 /// Requests the valid frequency range of each clock
 #[derive(Debug)]
-pub struct OperatingFrequencyInquiry {}
+pub struct OperatingFrequencyInquiry {
+    /// Byte order of the frequencies in the response, which is big-endian unless the device is
+    /// known to differ
+    pub endianness: Endianness,
+}
 
 impl TransmitCommandData for OperatingFrequencyInquiry {
     fn command_data(&self) -> CommandData {
@@ -37,8 +41,8 @@ impl Receive for OperatingFrequencyInquiry {
             let mut maximum_frequency_bytes = [0u8; 2];
             maximum_frequency_bytes.copy_from_slice(&clock_type_data[2..=3]);
 
-            let minimum_frequency = u16::from_be_bytes(minimum_frequency_bytes);
-            let maximum_frequency = u16::from_be_bytes(maximum_frequency_bytes);
+            let minimum_frequency = self.endianness.u16_from_bytes(minimum_frequency_bytes);
+            let maximum_frequency = self.endianness.u16_from_bytes(maximum_frequency_bytes);
 
             clock_types.push(minimum_frequency..=maximum_frequency);
 
@@ -56,7 +60,9 @@ mod tests {
 
     #[test]
     fn test_tx() -> Result<()> {
-        let cmd = OperatingFrequencyInquiry {};
+        let cmd = OperatingFrequencyInquiry {
+            endianness: Endianness::Big,
+        };
         let command_bytes = [0x23];
         let mut p = mock_io::Builder::new().write(&command_bytes).build();
 
@@ -69,7 +75,9 @@ mod tests {
 
     #[test]
     fn test_rx() {
-        let cmd = OperatingFrequencyInquiry {};
+        let cmd = OperatingFrequencyInquiry {
+            endianness: Endianness::Big,
+        };
         let response_bytes = [
             0x33, 0x09, 0x02, // Header
             0x03, 0xE8, 0x07, 0xD0, // Clock type 1
@@ -83,4 +91,23 @@ mod tests {
         assert_eq!(response, Ok(vec![1000..=2000, 100..=10000]));
         assert!(is_script_complete(&mut p));
     }
+
+    #[test]
+    fn test_rx_little_endian() {
+        let cmd = OperatingFrequencyInquiry {
+            endianness: Endianness::Little,
+        };
+        let response_bytes = [
+            0x33, 0x09, 0x02, // Header
+            0xE8, 0x03, 0xD0, 0x07, // Clock type 1
+            0x64, 0x00, 0x10, 0x27, // Clock type 2
+            0x65, // Checksum
+        ];
+        let mut p = mock_io::Builder::new().read(&response_bytes).build();
+
+        let response = cmd.rx(&mut p);
+
+        assert_eq!(response, Ok(vec![1000..=2000, 100..=10000]));
+        assert!(is_script_complete(&mut p));
+    }
 }
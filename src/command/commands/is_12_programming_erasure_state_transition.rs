@@ -6,7 +6,7 @@ use super::command_impl_prelude::*;
 pub struct ProgrammingErasureStateTransition {}
 
 /// Response to a `ProgrammingErasureStateTransition`
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum IDCodeProtectionStatus {
     /// ID code protection disabled, device now in programming/erasure command wait
     Disabled,
@@ -1,4 +1,7 @@
+use std::convert::TryFrom;
+
 use super::command_impl_prelude::*;
+use crate::{Error, ErrorKind};
 
 /// Requests the current status of the device
 #[derive(Debug)]
@@ -15,7 +18,7 @@ impl TransmitCommandData for BootProgramStatusInquiry {
 }
 
 /// The current status of the device
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BootProgramStatus {
     /// Waiting to be issued a `DeviceSelection` command
     WaitingForDeviceSelection,
@@ -35,9 +38,11 @@ pub enum BootProgramStatus {
     WaitingForErasureBlockSpecification,
 }
 
-impl From<u8> for BootProgramStatus {
-    fn from(item: u8) -> Self {
-        match item {
+impl TryFrom<u8> for BootProgramStatus {
+    type Error = Error;
+
+    fn try_from(item: u8) -> Result<Self> {
+        Ok(match item {
             0x11 => BootProgramStatus::WaitingForDeviceSelection,
             0x12 => BootProgramStatus::WaitingForClockModeSelection,
             0x13 => BootProgramStatus::WaitingForBitRateSelection,
@@ -46,13 +51,18 @@ impl From<u8> for BootProgramStatus {
             0x3F => BootProgramStatus::WaitingForProgrammingErasureCommand,
             0x4F => BootProgramStatus::WaitingForProgrammingData,
             0x5F => BootProgramStatus::WaitingForErasureBlockSpecification,
-            _ => panic!("Invalid status code"),
-        }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Protocol,
+                    format!("unknown boot program status {:#04X}", item),
+                ))
+            }
+        })
     }
 }
 
 /// Last error encountered
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BootProgramError {
     /// No errors
     NoError,
@@ -90,9 +100,11 @@ pub enum BootProgramError {
     BitRateAdjustmentConfirmation,
 }
 
-impl From<u8> for BootProgramError {
-    fn from(item: u8) -> Self {
-        match item {
+impl TryFrom<u8> for BootProgramError {
+    type Error = Error;
+
+    fn try_from(item: u8) -> Result<Self> {
+        Ok(match item {
             0x00 => BootProgramError::NoError,
             0x11 => BootProgramError::Checksum,
             0x21 => BootProgramError::IncorrectDeviceCode,
@@ -110,13 +122,18 @@ impl From<u8> for BootProgramError {
             0x54 => BootProgramError::Selection,
             0x80 => BootProgramError::Command,
             0xFF => BootProgramError::BitRateAdjustmentConfirmation,
-            _ => panic!("Invalid error code"),
-        }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Protocol,
+                    format!("unknown boot program error {:#04X}", item),
+                ))
+            }
+        })
     }
 }
 
 /// Response to a `BootProgramStatusInquiry`
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct BootProgramStatusInquiryResponse {
     /// Current device status
     pub status: BootProgramStatus,
@@ -132,9 +149,15 @@ impl Receive for BootProgramStatusInquiry {
             ResponseReader::<_, SizedResponse<u8>, NoError>::new(p, ResponseFirstByte::Byte(0x5F));
 
         let data = reader.read_response()?.data;
+        if data.len() != 2 {
+            return Err(Error::new(
+                ErrorKind::Protocol,
+                "boot program status response must contain 2 bytes",
+            ));
+        }
 
-        let status = BootProgramStatus::from(data[0]);
-        let error = BootProgramError::from(data[1]);
+        let status = BootProgramStatus::try_from(data[0])?;
+        let error = BootProgramError::try_from(data[1])?;
 
         Ok(BootProgramStatusInquiryResponse {
             status: status,
@@ -178,4 +201,28 @@ mod tests {
         );
         assert!(is_script_complete(&mut p));
     }
+
+    #[test]
+    fn test_rx_unknown_status() {
+        let cmd = BootProgramStatusInquiry {};
+        let response_bytes = [0x5F, 0x02, 0x14, 0x24, 0x67];
+        let mut p = mock_io::Builder::new().read(&response_bytes).build();
+
+        let response = cmd.rx(&mut p);
+
+        assert_eq!(response.map_err(|e| e.kind), Err(ErrorKind::Protocol));
+        assert!(is_script_complete(&mut p));
+    }
+
+    #[test]
+    fn test_rx_unknown_error() {
+        let cmd = BootProgramStatusInquiry {};
+        let response_bytes = [0x5F, 0x02, 0x13, 0x23, 0x69];
+        let mut p = mock_io::Builder::new().read(&response_bytes).build();
+
+        let response = cmd.rx(&mut p);
+
+        assert_eq!(response.map_err(|e| e.kind), Err(ErrorKind::Protocol));
+        assert!(is_script_complete(&mut p));
+    }
 }
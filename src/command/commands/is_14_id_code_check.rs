@@ -0,0 +1,117 @@
+use super::command_impl_prelude::*;
+use crate::{Error, ErrorKind};
+
+/// Submits the device's ID code, when ID code protection is enabled. If the ID code matches, the
+/// device transitions to the programming/erasure command wait.
+#[derive(Debug)]
+pub struct IDCodeCheck {
+    /// The ID code to check
+    pub id_code: [u8; 16],
+}
+
+impl TransmitCommandData for IDCodeCheck {
+    fn command_data(&self) -> CommandData {
+        CommandData {
+            opcode: 0x60,
+            has_size_field: true,
+            payload: self.id_code.to_vec(),
+        }
+    }
+}
+
+impl Receive for IDCodeCheck {
+    type Response = ();
+
+    fn rx<T: io::Read>(&self, p: &mut T) -> Result<Self::Response> {
+        let mut reader = ResponseReader::<_, SimpleResponse, WithError>::new(
+            p,
+            ResponseFirstByte::Byte(0x06),
+            ErrorFirstByte(0xE0),
+        );
+
+        reader
+            .read_response()?
+            .map(|_| ())
+            .map_err(|error_code| match error_code {
+                0x11 => CommandError::Checksum.into(),
+                0x61 => CommandError::IDCodeMismatch.into(),
+                0x63 => CommandError::Erasure.into(),
+                _ => Error::new(
+                    ErrorKind::Protocol,
+                    format!("unknown ID code check error code {:#04X}", error_code),
+                ),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util::is_script_complete;
+    use super::*;
+
+    fn id_code() -> [u8; 16] {
+        let mut id_code = [0u8; 16];
+        for (i, byte) in id_code.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        id_code
+    }
+
+    #[test]
+    fn test_tx() -> Result<()> {
+        let cmd = IDCodeCheck { id_code: id_code() };
+        let command_bytes = [
+            0x60, 0x10, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B,
+            0x0C, 0x0D, 0x0E, 0x0F, 0x18,
+        ];
+        let mut p = mock_io::Builder::new().write(&command_bytes).build();
+
+        cmd.tx(&mut p)?;
+
+        assert!(is_script_complete(&mut p));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rx_success() {
+        let cmd = IDCodeCheck { id_code: id_code() };
+        let response_bytes = [0x06];
+        let mut p = mock_io::Builder::new().read(&response_bytes).build();
+
+        let response = cmd.rx(&mut p);
+
+        assert_eq!(response, Ok(()));
+        assert!(is_script_complete(&mut p));
+    }
+
+    #[test]
+    fn test_rx_fail() {
+        let cmd = IDCodeCheck { id_code: id_code() };
+        let response_bytes = [0xE0, 0x61];
+        let mut p = mock_io::Builder::new().read(&response_bytes).build();
+
+        let response = cmd.rx(&mut p);
+
+        assert_eq!(response, Err(CommandError::IDCodeMismatch.into()));
+        assert!(is_script_complete(&mut p));
+    }
+
+    #[test]
+    fn test_rx_unknown_error_code() {
+        let cmd = IDCodeCheck { id_code: id_code() };
+        let response_bytes = [0xE0, 0x42];
+        let mut p = mock_io::Builder::new().read(&response_bytes).build();
+
+        let response = cmd.rx(&mut p);
+
+        assert_eq!(
+            response,
+            Err(Error::new(
+                ErrorKind::Protocol,
+                "unknown ID code check error code 0x42"
+            ))
+        );
+        assert!(is_script_complete(&mut p));
+    }
+}
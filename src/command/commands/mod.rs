@@ -14,6 +14,7 @@ mod is_11a_new_bit_rate_selection;
 mod is_11b_new_bit_rate_selection_confirmation;
 mod is_12_programming_erasure_state_transition;
 mod is_13_boot_program_status_inquiry;
+mod is_14_id_code_check;
 mod isd_01_data_area_inquiry;
 mod isd_02_data_area_information_inquiry;
 mod pe_01_user_boot_area_programming_selection;
@@ -51,6 +52,7 @@ pub use is_12_programming_erasure_state_transition::{
 pub use is_13_boot_program_status_inquiry::{
     BootProgramError, BootProgramStatus, BootProgramStatusInquiry, BootProgramStatusInquiryResponse,
 };
+pub use is_14_id_code_check::IDCodeCheck;
 pub use isd_01_data_area_inquiry::DataAreaInquiry;
 pub use isd_02_data_area_information_inquiry::DataAreaInformationInquiry;
 pub use pe_01_user_boot_area_programming_selection::UserBootAreaProgrammingSelection;
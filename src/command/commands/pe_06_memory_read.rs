@@ -18,10 +18,7 @@ impl TransmitCommandData for MemoryRead {
             has_size_field: true,
             payload: {
                 let mut payload = vec![];
-                payload.push(match self.area {
-                    MemoryArea::UserBootArea => 0x00,
-                    MemoryArea::UserArea => 0x01,
-                });
+                payload.push(u8::from(&self.area));
                 payload.extend(&self.start_address.to_be_bytes());
                 payload.extend(&self.size.to_be_bytes());
                 payload
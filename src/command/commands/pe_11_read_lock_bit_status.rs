@@ -20,10 +20,7 @@ impl TransmitCommandData for ReadLockBitStatus {
             has_size_field: true,
             payload: {
                 let mut payload = vec![];
-                payload.push(match self.area {
-                    MemoryArea::UserBootArea => 0x00,
-                    MemoryArea::UserArea => 0x01,
-                });
+                payload.push(u8::from(&self.area));
                 payload.push(self.a15_to_a8);
                 payload.push(self.a23_to_a16);
                 payload.push(self.a31_to_a24);
@@ -1,8 +1,95 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::{Error, ErrorKind, Result};
+
+/// The 4 character ASCII code identifying a device
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeviceCode(String);
+
+impl DeviceCode {
+    /// Creates a device code, which must be exactly 4 ASCII characters
+    ///
+    /// # Examples
+    /// ```
+    /// use rxprog::command::data::DeviceCode;
+    ///
+    /// assert_eq!(DeviceCode::new("7805").unwrap().as_str(), "7805");
+    /// assert!(DeviceCode::new("780").is_err());
+    /// assert!(DeviceCode::new("78055").is_err());
+    /// ```
+    pub fn new(code: &str) -> Result<DeviceCode> {
+        if code.len() != 4 || !code.is_ascii() {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                format!("device code \"{}\" is not 4 ASCII characters", code),
+            ));
+        }
+
+        Ok(DeviceCode(code.to_string()))
+    }
+
+    /// The device code as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for DeviceCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Byte order of multi-byte values in inquiry responses or device memory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endianness {
+    /// Most significant byte first, as documented for the Boot Mode protocol
+    #[default]
+    Big,
+    /// Least significant byte first, as sent by some devices
+    Little,
+}
+
+impl Endianness {
+    /// Decodes a 16-bit value in this byte order
+    ///
+    /// # Examples
+    /// ```
+    /// use rxprog::command::data::Endianness;
+    ///
+    /// assert_eq!(Endianness::Big.u16_from_bytes([0x12, 0x34]), 0x1234);
+    /// assert_eq!(Endianness::Little.u16_from_bytes([0x12, 0x34]), 0x3412);
+    /// ```
+    pub fn u16_from_bytes(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Endianness::Big => u16::from_be_bytes(bytes),
+            Endianness::Little => u16::from_le_bytes(bytes),
+        }
+    }
+
+    /// Decodes a 32-bit value in this byte order
+    ///
+    /// # Examples
+    /// ```
+    /// use rxprog::command::data::Endianness;
+    ///
+    /// assert_eq!(Endianness::Big.u32_from_bytes([0x12, 0x34, 0x56, 0x78]), 0x12345678);
+    /// assert_eq!(Endianness::Little.u32_from_bytes([0x12, 0x34, 0x56, 0x78]), 0x78563412);
+    /// ```
+    pub fn u32_from_bytes(self, bytes: [u8; 4]) -> u32 {
+        match self {
+            Endianness::Big => u32::from_be_bytes(bytes),
+            Endianness::Little => u32::from_le_bytes(bytes),
+        }
+    }
+}
+
 /// A device supported by the boot program
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SupportedDevice {
     /// A 4 character identifier
-    pub device_code: String,
+    pub device_code: DeviceCode,
     /// Human-readable name of the device
     pub series_name: String,
 }
@@ -16,26 +103,33 @@ pub enum MultiplicationRatio {
     MultiplyBy(u8),
 }
 
-impl From<u8> for MultiplicationRatio {
-    /// Parse a byte encoded ratio
+impl TryFrom<u8> for MultiplicationRatio {
+    type Error = Error;
+
+    /// Parse a byte encoded ratio, which cannot be zero
     ///
     /// # Examples
     /// ```
+    /// use std::convert::TryFrom;
     /// use rxprog::command::data::MultiplicationRatio;
     ///
-    /// assert_eq!(MultiplicationRatio::from(0xFF), MultiplicationRatio::DivideBy(1));
-    /// assert_eq!(MultiplicationRatio::from(0xFE), MultiplicationRatio::DivideBy(2));
-    /// assert_eq!(MultiplicationRatio::from(0x01), MultiplicationRatio::MultiplyBy(1));
-    /// assert_eq!(MultiplicationRatio::from(0x02), MultiplicationRatio::MultiplyBy(2));
+    /// assert_eq!(MultiplicationRatio::try_from(0xFF), Ok(MultiplicationRatio::DivideBy(1)));
+    /// assert_eq!(MultiplicationRatio::try_from(0xFE), Ok(MultiplicationRatio::DivideBy(2)));
+    /// assert_eq!(MultiplicationRatio::try_from(0x01), Ok(MultiplicationRatio::MultiplyBy(1)));
+    /// assert_eq!(MultiplicationRatio::try_from(0x02), Ok(MultiplicationRatio::MultiplyBy(2)));
+    /// assert!(MultiplicationRatio::try_from(0x00).is_err());
     /// ```
-    fn from(item: u8) -> Self {
+    fn try_from(item: u8) -> Result<Self> {
         let item_signed = i8::from_le_bytes([item]);
-        let ratio = item_signed.abs() as u8;
+        let ratio = item_signed.unsigned_abs();
 
         match item_signed {
-            x if x < 0 => MultiplicationRatio::DivideBy(ratio),
-            x if x > 0 => MultiplicationRatio::MultiplyBy(ratio),
-            _ => panic!("Multiplication ratio cannot be zero"),
+            x if x < 0 => Ok(MultiplicationRatio::DivideBy(ratio)),
+            x if x > 0 => Ok(MultiplicationRatio::MultiplyBy(ratio)),
+            _ => Err(Error::new(
+                ErrorKind::Protocol,
+                "multiplication ratio cannot be zero",
+            )),
         }
     }
 }
@@ -61,8 +155,83 @@ impl From<MultiplicationRatio> for u8 {
     }
 }
 
+/// A device input frequency, stored in the protocol's encoding of MHz * 100
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputFrequency(u16);
+
+impl InputFrequency {
+    /// Creates an input frequency from a value in Hz. The protocol's encoding has a resolution of
+    /// 10 kHz, so the value is rounded to the nearest 10 kHz.
+    ///
+    /// # Examples
+    /// ```
+    /// use rxprog::command::data::InputFrequency;
+    ///
+    /// assert_eq!(u16::from(InputFrequency::from_hz(32_000_000).unwrap()), 3200);
+    /// assert_eq!(u16::from(InputFrequency::from_hz(12_288_000).unwrap()), 1229);
+    /// assert!(InputFrequency::from_hz(1_000_000_000).is_err());
+    /// ```
+    pub fn from_hz(hz: u32) -> Result<InputFrequency> {
+        let encoded = (hz as u64 + 5_000) / 10_000;
+
+        if encoded > u16::MAX as u64 {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                format!("input frequency of {} Hz is too high", hz),
+            ));
+        }
+
+        Ok(InputFrequency(encoded as u16))
+    }
+
+    /// Creates an input frequency from a value in MHz, rounded to the nearest 10 kHz
+    ///
+    /// # Examples
+    /// ```
+    /// use rxprog::command::data::InputFrequency;
+    ///
+    /// assert_eq!(u16::from(InputFrequency::from_mhz(32.0).unwrap()), 3200);
+    /// assert_eq!(u16::from(InputFrequency::from_mhz(12.288).unwrap()), 1229);
+    /// assert!(InputFrequency::from_mhz(-1.0).is_err());
+    /// ```
+    pub fn from_mhz(mhz: f32) -> Result<InputFrequency> {
+        let encoded = (mhz * 100.0).round();
+
+        // Written so that NaN is also rejected
+        if !(encoded >= 0.0 && encoded <= u16::MAX as f32) {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                format!("input frequency of {} MHz is out of range", mhz),
+            ));
+        }
+
+        Ok(InputFrequency(encoded as u16))
+    }
+}
+
+impl From<u16> for InputFrequency {
+    /// Interpret a value already in the protocol's MHz * 100 encoding
+    ///
+    /// # Examples
+    /// ```
+    /// use rxprog::command::data::InputFrequency;
+    ///
+    /// assert_eq!(InputFrequency::from(3200), InputFrequency::from_mhz(32.0).unwrap());
+    /// ```
+    fn from(item: u16) -> Self {
+        InputFrequency(item)
+    }
+}
+
+impl From<InputFrequency> for u16 {
+    /// Convert the frequency to the protocol's MHz * 100 encoding
+    fn from(item: InputFrequency) -> Self {
+        item.0
+    }
+}
+
 /// Availability state of a data area
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum DataAreaAvailability {
     /// The device supports a data area
     Available,
@@ -71,7 +240,7 @@ pub enum DataAreaAvailability {
 }
 
 /// A distinct region of memory
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum MemoryArea {
     /// User boot area, i.e. user specified bootloader
     UserBootArea,
@@ -79,8 +248,54 @@ pub enum MemoryArea {
     UserArea,
 }
 
-/// State of the block
-#[derive(Debug, PartialEq)]
+impl TryFrom<u8> for MemoryArea {
+    type Error = Error;
+
+    /// Parse a byte encoded memory area
+    ///
+    /// # Examples
+    /// ```
+    /// use std::convert::TryFrom;
+    /// use rxprog::command::data::MemoryArea;
+    ///
+    /// assert_eq!(MemoryArea::try_from(0x00), Ok(MemoryArea::UserBootArea));
+    /// assert_eq!(MemoryArea::try_from(0x01), Ok(MemoryArea::UserArea));
+    /// assert!(MemoryArea::try_from(0x02).is_err());
+    /// ```
+    fn try_from(item: u8) -> Result<Self> {
+        match item {
+            0x00 => Ok(MemoryArea::UserBootArea),
+            0x01 => Ok(MemoryArea::UserArea),
+            _ => Err(Error::new(
+                ErrorKind::Protocol,
+                format!("unknown memory area {:#04X}", item),
+            )),
+        }
+    }
+}
+
+impl From<&MemoryArea> for u8 {
+    /// Convert the area to the byte used to select it in commands
+    ///
+    /// # Examples
+    /// ```
+    /// use rxprog::command::data::MemoryArea;
+    ///
+    /// assert_eq!(u8::from(&MemoryArea::UserBootArea), 0x00);
+    /// assert_eq!(u8::from(&MemoryArea::UserArea), 0x01);
+    /// ```
+    fn from(item: &MemoryArea) -> Self {
+        match item {
+            MemoryArea::UserBootArea => 0x00,
+            MemoryArea::UserArea => 0x01,
+        }
+    }
+}
+
+/// Erasure state of a memory area, as returned by all of the blank check
+/// commands (`UserBootAreaBlankCheck`, `UserAreaBlankCheck` and
+/// `DataAreaBlankCheck`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ErasureState {
     /// No blocks programmed
     Blank,
@@ -89,7 +304,7 @@ pub enum ErasureState {
 }
 
 /// The state of the lock bit protecting a memory region
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum LockBitStatus {
     /// Lock bit set - write/erase disallowed
     Locked,
@@ -2,8 +2,10 @@ use std::io;
 use std::marker::PhantomData;
 use std::mem;
 
+use crate::{Error, ErrorKind};
+
 pub trait ResponseBody: Sized {
-    fn read_body<T: io::Read>(p: &mut T, first_byte: u8) -> io::Result<Self>;
+    fn read_body<T: io::Read>(p: &mut T, first_byte: u8) -> crate::Result<Self>;
 }
 
 #[derive(Debug, PartialEq)]
@@ -12,7 +14,7 @@ pub struct SimpleResponse {
 }
 
 impl ResponseBody for SimpleResponse {
-    fn read_body<T: io::Read>(_p: &mut T, first_byte: u8) -> io::Result<SimpleResponse> {
+    fn read_body<T: io::Read>(_p: &mut T, first_byte: u8) -> crate::Result<SimpleResponse> {
         Ok(SimpleResponse {
             first_byte: first_byte,
         })
@@ -53,6 +55,32 @@ impl ResponseSize for u32 {
     }
 }
 
+// Like `read_exact`, but returns how many bytes were read if the response ends
+// early, rather than discarding the partial read
+fn read_until_timeout<T: io::Read>(p: &mut T, buf: &mut [u8]) -> io::Result<usize> {
+    let mut received = 0;
+    while received < buf.len() {
+        match p.read(&mut buf[received..]) {
+            Ok(0) => break,
+            Ok(n) => received += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(received)
+}
+
+// A device at the wrong bit rate or in an unexpected state can send anything,
+// so this is an error rather than a panic
+fn unknown_first_byte(first_byte: u8) -> Error {
+    Error::new(
+        ErrorKind::Protocol,
+        format!("unexpected response first byte {:#04X}", first_byte),
+    )
+}
+
 #[derive(Debug, PartialEq)]
 pub struct SizedResponse<T: ResponseSize> {
     pub data: Vec<u8>,
@@ -61,11 +89,20 @@ pub struct SizedResponse<T: ResponseSize> {
 }
 
 impl<T: ResponseSize> ResponseBody for SizedResponse<T> {
-    fn read_body<U: io::Read>(p: &mut U, _first_byte: u8) -> io::Result<SizedResponse<T>> {
+    fn read_body<U: io::Read>(p: &mut U, _first_byte: u8) -> crate::Result<SizedResponse<T>> {
         let size = T::read_size(p)?;
 
         let mut data = vec![0u8; size];
-        p.read_exact(&mut data)?;
+        let received = read_until_timeout(p, &mut data)?;
+        if received != size {
+            return Err(Error::new(
+                ErrorKind::Protocol,
+                format!(
+                    "truncated response: expected {} bytes, received {}",
+                    size, received
+                ),
+            ));
+        }
 
         let mut _checksum = [0u8; 1];
         p.read_exact(&mut _checksum)?;
@@ -144,7 +181,7 @@ impl<T: io::Read, TResponse: ResponseBody> ResponseReader<T, TResponse, NoError>
 impl<T: io::Read, TResponse: ResponseBody, TError> ResponseReader<T, TResponse, TError> {
     fn read_first_byte(&mut self) -> io::Result<u8> {
         let mut first_byte = [0u8; 1];
-        self.p.read(&mut first_byte)?;
+        self.p.read_exact(&mut first_byte)?;
         let first_byte = first_byte[0];
 
         Ok(first_byte)
@@ -163,12 +200,12 @@ impl<T: io::Read, TResponse: ResponseBody> ResponseReader<T, TResponse, WithErro
         first_byte == self.error_first_byte.unwrap()
     }
 
-    pub fn read_response(&mut self) -> io::Result<Result<TResponse, u8>> {
+    pub fn read_response(&mut self) -> crate::Result<Result<TResponse, u8>> {
         let first_byte = self.read_first_byte()?;
 
         if self.is_valid_error_first_byte(first_byte) {
             let mut error_code = [0u8; 1];
-            self.p.read(&mut error_code)?;
+            self.p.read_exact(&mut error_code)?;
             let error_code = error_code[0];
 
             return Ok(Err(error_code));
@@ -178,19 +215,19 @@ impl<T: io::Read, TResponse: ResponseBody> ResponseReader<T, TResponse, WithErro
             return Ok(Ok(TResponse::read_body(&mut self.p, first_byte)?));
         }
 
-        panic!("Unknown first byte");
+        Err(unknown_first_byte(first_byte))
     }
 }
 
 impl<T: io::Read, TResponse: ResponseBody> ResponseReader<T, TResponse, NoError> {
-    pub fn read_response(&mut self) -> io::Result<TResponse> {
+    pub fn read_response(&mut self) -> crate::Result<TResponse> {
         let first_byte = self.read_first_byte()?;
 
         if self.is_valid_response_first_byte(first_byte) {
             return Ok(TResponse::read_body(&mut self.p, first_byte)?);
         }
 
-        panic!("Unknown first byte");
+        Err(unknown_first_byte(first_byte))
     }
 }
 
@@ -200,20 +237,21 @@ mod tests {
     use super::*;
 
     macro_rules! make_test {
-        (name => $n:ident, response => $r:expr, rr => $rr:expr, result => panic) => {
+        (name => $n:ident, response => $r:expr, rr => $rr:expr, result => protocol_error) => {
             #[test]
-            #[should_panic]
             fn $n() {
                 let mut p = mock_io::Builder::new().read(&$r).build();
                 let mut rr = $rr(&mut p);
 
-                let _response = rr.read_response();
+                let response = rr.read_response();
+
+                assert_eq!(response.err().map(|e| e.kind), Some(ErrorKind::Protocol));
             }
         };
 
         (name => $n:ident, response => $r:expr, rr => $rr:expr, result => $re:expr) => {
             #[test]
-            fn $n() -> io::Result<()> {
+            fn $n() -> crate::Result<()> {
                 let mut p = mock_io::Builder::new().read(&$r).build();
                 let mut rr = $rr(&mut p);
 
@@ -262,7 +300,7 @@ mod tests {
                 ResponseFirstByte::Byte(0x20),
                 ErrorFirstByte(0x30)
             ),
-            result => panic
+            result => protocol_error
         );
     }
 
@@ -288,7 +326,7 @@ mod tests {
                 p,
                 ResponseFirstByte::Byte(0x20),
             ),
-            result => panic
+            result => protocol_error
         );
     }
 
@@ -329,7 +367,27 @@ mod tests {
                 ResponseFirstByte::Byte(0x20),
                 ErrorFirstByte(0x30)
             ),
-            result => panic
+            result => protocol_error
+        );
+    }
+
+    #[test]
+    fn sized_response_truncated() {
+        let mut p = mock_io::Builder::new()
+            .read(&[0x20, 0x04, 0x12, 0x34])
+            .build();
+        let mut rr = ResponseReader::<_, SizedResponse<u8>, NoError>::new(
+            &mut p,
+            ResponseFirstByte::Byte(0x20),
+        );
+
+        let response = rr.read_response();
+
+        let error = response.err().unwrap();
+        assert_eq!(error.kind, ErrorKind::Protocol);
+        assert_eq!(
+            error.description,
+            "truncated response: expected 4 bytes, received 2"
         );
     }
 
@@ -357,7 +415,7 @@ mod tests {
                 p,
                 ResponseFirstByte::Byte(0x20),
             ),
-            result => panic
+            result => protocol_error
         );
     }
 
@@ -398,7 +456,7 @@ mod tests {
                 ResponseFirstByte::Byte(0x20),
                 ErrorFirstByte(0x30)
             ),
-            result => panic
+            result => protocol_error
         );
     }
 
@@ -426,7 +484,7 @@ mod tests {
                 p,
                 ResponseFirstByte::Byte(0x20),
             ),
-            result => panic
+            result => protocol_error
         );
     }
 
@@ -467,7 +525,7 @@ mod tests {
                 ResponseFirstByte::Byte(0x20),
                 ErrorFirstByte(0x30)
             ),
-            result => panic
+            result => protocol_error
         );
     }
 
@@ -495,7 +553,7 @@ mod tests {
                 p,
                 ResponseFirstByte::Byte(0x20),
             ),
-            result => panic
+            result => protocol_error
         );
     }
 }
@@ -0,0 +1,1103 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::num::Wrapping;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+const UNPROGRAMMED_BYTE: u8 = 0xFF;
+
+#[derive(Debug, PartialEq)]
+struct Region {
+    address_range: RangeInclusive<u32>,
+    data: Vec<u8>,
+}
+
+/// A firmware image, covering a set of memory regions. Bytes not populated with
+/// data are left as the device's erased value, `0xFF` unless otherwise
+/// specified.
+#[derive(Debug, PartialEq)]
+pub struct Image {
+    regions: Vec<Region>,
+    erased_value: u8,
+    start_address: Option<u32>,
+}
+
+/// Formats of image file which can be loaded into an image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    /// Intel HEX
+    Ihex,
+    /// Motorola S-record
+    Srec,
+}
+
+impl ImageFormat {
+    /// Guesses the format of an image file from its extension, returning
+    /// `None` if the extension isn't recognised
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use rxprog::image::ImageFormat;
+    ///
+    /// assert_eq!(ImageFormat::from_path(Path::new("blink.hex")), Some(ImageFormat::Ihex));
+    /// assert_eq!(ImageFormat::from_path(Path::new("blink.mot")), Some(ImageFormat::Srec));
+    /// assert_eq!(ImageFormat::from_path(Path::new("blink.bin")), None);
+    /// ```
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        match path.extension()?.to_str()? {
+            "hex" | "ihex" | "ihx" => Some(ImageFormat::Ihex),
+            "srec" | "mot" => Some(ImageFormat::Srec),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ImageFormat::Ihex => "ihex",
+            ImageFormat::Srec => "srec",
+        })
+    }
+}
+
+/// A contiguous block of an image, suitable for programming in one command
+#[derive(Debug, PartialEq)]
+pub struct Block<'a> {
+    /// Address of the first byte of the block
+    pub start_address: u32,
+    /// Data contained in the block
+    pub data: &'a [u8],
+    /// The device's erased value, which marks bytes not populated by the image
+    pub erased_value: u8,
+}
+
+impl Block<'_> {
+    /// Compares the block against data read back from the device, ignoring
+    /// bytes which were not populated by the image
+    pub fn matches(&self, read_back: &[u8]) -> bool {
+        // Bytes the image didn't populate are left to whatever the device holds
+        // (normally the erased value), so only populated bytes are compared
+        self.data.len() == read_back.len()
+            && self
+                .data
+                .iter()
+                .zip(read_back)
+                .all(|(&expected, &actual)| expected == self.erased_value || expected == actual)
+    }
+}
+
+/// A block of an image which owns its data, so it can be prepared on one
+/// thread and programmed from another
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedBlock {
+    /// Address of the first byte of the block
+    pub start_address: u32,
+    /// Data contained in the block
+    pub data: Vec<u8>,
+}
+
+impl From<Block<'_>> for OwnedBlock {
+    fn from(block: Block<'_>) -> OwnedBlock {
+        OwnedBlock {
+            start_address: block.start_address,
+            data: block.data.to_vec(),
+        }
+    }
+}
+
+impl Image {
+    /// Creates an empty image covering the provided regions
+    pub fn new(regions: &[RangeInclusive<u32>]) -> Image {
+        Image::with_erased_value(regions, UNPROGRAMMED_BYTE)
+    }
+
+    /// Creates an empty image covering the provided regions, for a device
+    /// whose erased flash reads as `erased_value`. The boot program doesn't
+    /// report the erased value, so it must be known ahead of time.
+    pub fn with_erased_value(regions: &[RangeInclusive<u32>], erased_value: u8) -> Image {
+        let regions = regions
+            .iter()
+            .map(|address_range| {
+                let length = address_range.end() - address_range.start() + 1;
+                let data = vec![erased_value; length as usize];
+
+                Region {
+                    address_range: address_range.clone(),
+                    data,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Image {
+            regions,
+            erased_value,
+            start_address: None,
+        }
+    }
+
+    /// The value of bytes not populated by the image
+    pub fn erased_value(&self) -> u8 {
+        self.erased_value
+    }
+
+    /// The address execution should begin at, if specified by the image file
+    pub fn start_address(&self) -> Option<u32> {
+        self.start_address
+    }
+
+    /// Sets the address execution should begin at
+    pub fn set_start_address(&mut self, start_address: Option<u32>) {
+        self.start_address = start_address;
+    }
+
+    /// Copies data into the image at the specified address
+    ///
+    /// # Panics
+    /// Panics if the address is not within one of the image's regions.
+    pub fn add_data(&mut self, address: u32, data: &[u8]) {
+        let region = self
+            .regions
+            .iter_mut()
+            .find(|region| region.address_range.contains(&address))
+            .expect(format!("region containing address {} must exist", address).as_str());
+
+        let offset = (address - region.address_range.start()) as usize;
+        region.data[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    /// Finds the parts of `records` (address and data pairs) which fall outside
+    /// of the image's regions, and so can't be added with `add_data`.
+    /// Contiguous uncovered bytes are returned as a single range. Data which
+    /// runs past the end of the address space can't be added either, and is
+    /// reported as a range ending at the last address.
+    pub fn check_coverage(&self, records: &[(u32, &[u8])]) -> Vec<RangeInclusive<u32>> {
+        let mut uncovered: Vec<RangeInclusive<u32>> = vec![];
+
+        for &(address, data) in records {
+            for offset in 0..data.len() {
+                let address = match u32::try_from(address as u64 + offset as u64) {
+                    Ok(address) => address,
+                    Err(_) => {
+                        match uncovered.last_mut() {
+                            Some(range) if *range.end() == u32::MAX => {}
+                            _ => uncovered.push(u32::MAX..=u32::MAX),
+                        }
+                        break;
+                    }
+                };
+                if self.contains_address(address) {
+                    continue;
+                }
+
+                match uncovered.last_mut() {
+                    Some(range) if range.end().checked_add(1) == Some(address) => {
+                        *range = *range.start()..=address;
+                    }
+                    _ => uncovered.push(address..=address),
+                }
+            }
+        }
+
+        uncovered
+    }
+
+    /// Adds the parts of `data` which fall within the image's regions, and
+    /// appends the ranges which don't to `uncovered`
+    fn add_covered_data(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        uncovered: &mut Vec<RangeInclusive<u32>>,
+    ) {
+        let missing = self.check_coverage(&[(address, data)]);
+        if missing.is_empty() {
+            self.add_data(address, data);
+            return;
+        }
+
+        for (offset, byte) in data.iter().enumerate() {
+            match address.checked_add(offset as u32) {
+                Some(address) if self.contains_address(address) => {
+                    self.add_data(address, &[*byte]);
+                }
+                _ => (),
+            }
+        }
+
+        for range in missing {
+            match uncovered.last_mut() {
+                Some(last) if last.end().checked_add(1) == Some(*range.start()) => {
+                    *last = *last.start()..=*range.end();
+                }
+                _ => uncovered.push(range),
+            }
+        }
+    }
+
+    /// Copies the populated bytes of another image, which must cover the same
+    /// regions and have the same erased value, into this image. If both images
+    /// populate the same byte, the image is left unchanged and the address of
+    /// the first overlapping byte is returned as an error. The other image's
+    /// start address is only used if this image doesn't have one.
+    pub fn merge(&mut self, other: &Image) -> Result<(), u32> {
+        assert!(
+            self.regions
+                .iter()
+                .map(|region| &region.address_range)
+                .eq(other.regions.iter().map(|region| &region.address_range)),
+            "merged images must have the same regions"
+        );
+        assert_eq!(
+            self.erased_value, other.erased_value,
+            "merged images must have the same erased value"
+        );
+
+        // Check for overlaps before copying anything, so a failed merge leaves
+        // the image untouched
+        for (region, other_region) in self.regions.iter().zip(&other.regions) {
+            let overlap = region
+                .data
+                .iter()
+                .zip(&other_region.data)
+                .position(|(&a, &b)| a != self.erased_value && b != self.erased_value);
+
+            if let Some(offset) = overlap {
+                return Err(region.address_range.start() + offset as u32);
+            }
+        }
+
+        for (region, other_region) in self.regions.iter_mut().zip(&other.regions) {
+            for (a, &b) in region.data.iter_mut().zip(&other_region.data) {
+                if b != self.erased_value {
+                    *a = b;
+                }
+            }
+        }
+
+        if self.start_address.is_none() {
+            self.start_address = other.start_address;
+        }
+
+        Ok(())
+    }
+
+    /// Describes each region of the image, its address range, and how many of
+    /// its bytes are populated with data or left as the fill value, one
+    /// region per line
+    pub fn memory_map_string(&self) -> String {
+        self.regions
+            .iter()
+            .map(|region| {
+                let populated = region
+                    .data
+                    .iter()
+                    .filter(|&&x| x != self.erased_value)
+                    .count();
+                let fill = region.data.len() - populated;
+
+                format!(
+                    "{:#010X}-{:#010X}: {} bytes populated, {} bytes fill\n",
+                    region.address_range.start(),
+                    region.address_range.end(),
+                    populated,
+                    fill
+                )
+            })
+            .collect()
+    }
+
+    /// Returns each contiguous run of populated (non-fill) bytes, in address
+    /// order, e.g. to find gaps between sections which should be contiguous
+    pub fn populated_ranges(&self) -> Vec<RangeInclusive<u32>> {
+        let mut regions = self.regions.iter().collect::<Vec<_>>();
+        regions.sort_by_key(|region| *region.address_range.start());
+
+        let mut ranges: Vec<RangeInclusive<u32>> = vec![];
+        for region in regions {
+            let start = *region.address_range.start();
+            for (offset, &x) in region.data.iter().enumerate() {
+                if x == self.erased_value {
+                    continue;
+                }
+
+                let address = start + offset as u32;
+                match ranges.last_mut() {
+                    Some(range) if *range.end() + 1 == address => {
+                        *range = *range.start()..=address;
+                    }
+                    _ => ranges.push(address..=address),
+                }
+            }
+        }
+
+        ranges
+    }
+
+    /// Partitions the image into an image of the user boot area and an image of
+    /// the user area, each covering the provided regions. Data outside of both
+    /// areas is discarded.
+    pub fn split_by_areas(
+        &self,
+        user_boot: &[RangeInclusive<u32>],
+        user: &[RangeInclusive<u32>],
+    ) -> (Image, Image) {
+        let mut user_boot_image = Image::with_erased_value(user_boot, self.erased_value);
+        let mut user_image = Image::with_erased_value(user, self.erased_value);
+
+        self.copy_populated_into(&mut user_boot_image);
+        self.copy_populated_into(&mut user_image);
+
+        // The start address stays with whichever area contains it
+        if let Some(start_address) = self.start_address {
+            for image in [&mut user_boot_image, &mut user_image].iter_mut() {
+                if image.contains_address(start_address) {
+                    image.start_address = Some(start_address);
+                }
+            }
+        }
+
+        (user_boot_image, user_image)
+    }
+
+    fn contains_address(&self, address: u32) -> bool {
+        self.regions
+            .iter()
+            .any(|region| region.address_range.contains(&address))
+    }
+
+    fn copy_populated_into(&self, image: &mut Image) {
+        for region in &self.regions {
+            let start = *region.address_range.start();
+            for (offset, &byte) in region.data.iter().enumerate() {
+                if byte == self.erased_value {
+                    continue;
+                }
+
+                let address = start + offset as u32;
+                let other_region = image
+                    .regions
+                    .iter_mut()
+                    .find(|other_region| other_region.address_range.contains(&address));
+
+                if let Some(other_region) = other_region {
+                    let other_offset = address - other_region.address_range.start();
+                    other_region.data[other_offset as usize] = byte;
+                }
+            }
+        }
+    }
+
+    /// Resets every byte outside of `ranges` to the unprogrammed value, so
+    /// only data within `ranges` is programmed
+    pub fn mask_to(&mut self, ranges: &[RangeInclusive<u32>]) {
+        for region in &mut self.regions {
+            let start = *region.address_range.start();
+            for (offset, byte) in region.data.iter_mut().enumerate() {
+                let address = start + offset as u32;
+                if !ranges.iter().any(|range| range.contains(&address)) {
+                    *byte = self.erased_value;
+                }
+            }
+        }
+    }
+
+    /// Adds the data from an Intel HEX file to the image, including the start
+    /// address from a start linear address record. Data outside of the image's
+    /// regions is skipped, and the ranges it covers are returned.
+    #[cfg(feature = "ihex")]
+    pub fn add_data_from_ihex(
+        &mut self,
+        reader: ihex::Reader,
+    ) -> Result<Vec<RangeInclusive<u32>>, ihex::ReaderError> {
+        let mut uncovered = vec![];
+        let mut base_address = 0u32;
+        for record in reader {
+            match record? {
+                ihex::Record::Data {
+                    offset,
+                    value: data,
+                } => {
+                    let address = base_address.wrapping_add(offset as u32);
+                    self.add_covered_data(address, &data, &mut uncovered);
+                }
+                ihex::Record::ExtendedSegmentAddress(esa) => base_address = (esa as u32) << 4,
+                ihex::Record::ExtendedLinearAddress(ela) => base_address = (ela as u32) << 16,
+                ihex::Record::StartLinearAddress(sla) => self.start_address = Some(sla),
+                _ => (),
+            }
+        }
+
+        Ok(uncovered)
+    }
+
+    /// Adds the data from a Motorola S-record file to the image, including the
+    /// start address from an S7, S8 or S9 record. Data outside of the image's
+    /// regions is skipped, and the ranges it covers are returned.
+    #[cfg(feature = "srec")]
+    pub fn add_data_from_srec(
+        &mut self,
+        records: impl Iterator<Item = Result<srec::Record, srec::ReaderError>>,
+    ) -> Result<Vec<RangeInclusive<u32>>, srec::ReaderError> {
+        let mut uncovered = vec![];
+        for record in records {
+            match record? {
+                srec::Record::S1(d) => {
+                    self.add_covered_data(d.address.into(), &d.data, &mut uncovered)
+                }
+                srec::Record::S2(d) => {
+                    self.add_covered_data(d.address.into(), &d.data, &mut uncovered)
+                }
+                srec::Record::S3(d) => {
+                    self.add_covered_data(d.address.into(), &d.data, &mut uncovered)
+                }
+                srec::Record::S7(address) => self.start_address = Some(address.into()),
+                srec::Record::S8(address) => self.start_address = Some(address.into()),
+                srec::Record::S9(address) => self.start_address = Some(address.into()),
+                _ => (),
+            }
+        }
+
+        Ok(uncovered)
+    }
+
+    /// Adds the data from the contents of an image file in the specified
+    /// format to the image. Data outside of the image's regions is skipped,
+    /// and the ranges it covers are returned.
+    #[cfg(all(feature = "ihex", feature = "srec"))]
+    pub fn add_data_from_str(
+        &mut self,
+        contents: &str,
+        format: ImageFormat,
+    ) -> crate::Result<Vec<RangeInclusive<u32>>> {
+        let result = match format {
+            ImageFormat::Ihex => self
+                .add_data_from_ihex(ihex::Reader::new(contents))
+                .map_err(|e| e.to_string()),
+            ImageFormat::Srec => self
+                .add_data_from_srec(srec::read_records(contents))
+                .map_err(|e| e.to_string()),
+        };
+
+        result.map_err(|e| {
+            crate::Error::new(
+                crate::ErrorKind::Argument,
+                format!("failed to parse {} ({})", format, e),
+            )
+        })
+    }
+
+    /// Loads an image file covering the provided regions, detecting its format
+    /// from its extension. Data outside of the regions is an error.
+    #[cfg(all(feature = "ihex", feature = "srec"))]
+    pub fn from_path(path: &Path, regions: &[RangeInclusive<u32>]) -> crate::Result<Image> {
+        let format = ImageFormat::from_path(path).ok_or_else(|| {
+            crate::Error::new(
+                crate::ErrorKind::Argument,
+                format!("could not determine image type of {}", path.display()),
+            )
+        })?;
+        let contents = std::fs::read_to_string(path)?;
+
+        let mut image = Image::new(regions);
+        let uncovered = image.add_data_from_str(&contents, format)?;
+        if let Some(range) = uncovered.first() {
+            return Err(crate::Error::new(
+                crate::ErrorKind::Argument,
+                format!(
+                    "{} contains data outside of the image's regions ({:#X}-{:#X})",
+                    path.display(),
+                    range.start(),
+                    range.end()
+                ),
+            ));
+        }
+
+        Ok(image)
+    }
+
+    /// Splits the image into blocks of `block_length` bytes, skipping blocks
+    /// which contain no data. Blocks start at multiples of `block_length` from
+    /// the start of their region (device regions are always aligned to the
+    /// programming unit), so data which doesn't start on a block boundary is
+    /// returned at its offset within the block, surrounded by fill bytes.
+    pub fn programmable_blocks(&self, block_length: usize) -> impl Iterator<Item = Block<'_>> + '_ {
+        self.regions
+            .iter()
+            .flat_map(move |region| {
+                region
+                    .data
+                    .chunks_exact(block_length)
+                    .enumerate()
+                    .map(move |(i, chunk)| {
+                        let start_address =
+                            *region.address_range.start() + (i * block_length) as u32;
+
+                        Block {
+                            start_address,
+                            data: chunk,
+                            erased_value: self.erased_value,
+                        }
+                    })
+            })
+            .filter(move |block| !block.data.iter().all(|&x| x == self.erased_value))
+    }
+
+    /// Returns the blocks `programmable_blocks` would return for the same
+    /// `block_length`, copied so they no longer borrow the image
+    pub fn owned_programmable_blocks(&self, block_length: usize) -> Vec<OwnedBlock> {
+        self.programmable_blocks(block_length)
+            .map(OwnedBlock::from)
+            .collect()
+    }
+
+    /// Counts the bytes in the blocks `programmable_blocks` would return for
+    /// the same `block_length`, including fill bytes within those blocks
+    pub fn total_programmable_bytes(&self, block_length: usize) -> usize {
+        self.programmable_block_count(block_length) * block_length
+    }
+
+    /// Estimates the number of bytes sent and received over the serial link
+    /// to program the blocks `programmable_blocks` would return for the same
+    /// `block_length`, including the overhead of the programming commands
+    pub fn estimated_transfer_bytes(&self, block_length: usize) -> usize {
+        // Every block is sent as 256 bytes in a programming command (opcode,
+        // address, data and checksum), and acknowledged with a single byte
+        const BLOCK_BYTES: usize = 1 + 4 + 256 + 1 + 1;
+        // Programming is ended by a programming command with no data
+        const END_BYTES: usize = 1 + 4 + 1 + 1;
+
+        self.programmable_block_count(block_length) * BLOCK_BYTES + END_BYTES
+    }
+
+    /// Counts the blocks `programmable_blocks` would return for the same
+    /// `block_length`, e.g. to size a progress bar before programming
+    pub fn programmable_block_count(&self, block_length: usize) -> usize {
+        self.regions
+            .iter()
+            .flat_map(|region| region.data.chunks_exact(block_length))
+            .filter(|chunk| !chunk.iter().all(|&x| x == self.erased_value))
+            .count()
+    }
+
+    /// Calculates the checksum the boot program reports for the image's
+    /// regions once programmed: the 32-bit wrapping sum of every byte,
+    /// including unprogrammed bytes
+    pub fn checksum(&self) -> u32 {
+        self.regions
+            .iter()
+            .flat_map(|region| region.data.iter())
+            .map(|&x| Wrapping(x as u32))
+            .sum::<Wrapping<u32>>()
+            .0
+    }
+
+    /// Calculates the checksum the boot program reports for the user boot
+    /// area (`UserBootAreaChecksum`), for an image created with the regions
+    /// returned by `user_boot_area`
+    ///
+    /// The boot program sums every byte of the user boot area as an unsigned
+    /// 32-bit value, discarding any carry out of the top bit. Erased bytes
+    /// (`0xFF`) are included in the sum, so a blank 16 KiB user boot area has
+    /// a checksum of `0x3FC000`. This is the same algorithm the boot program
+    /// uses for the user area, so this is equivalent to `checksum`.
+    pub fn user_boot_area_checksum(&self) -> u32 {
+        self.checksum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_returns_empty_image() {
+        let i = Image::new(&[0x0..=0xF, 0x20..=0x2F]);
+
+        assert_eq!(
+            i,
+            Image {
+                erased_value: UNPROGRAMMED_BYTE,
+                start_address: None,
+                regions: vec![
+                    Region {
+                        address_range: 0x0..=0xF,
+                        data: vec![UNPROGRAMMED_BYTE; 0x10]
+                    },
+                    Region {
+                        address_range: 0x20..=0x2F,
+                        data: vec![UNPROGRAMMED_BYTE; 0x10]
+                    }
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn add_data_inserts_data_correctly() {
+        let mut i = Image::new(&[0x0..=0xF, 0x20..=0x2F]);
+
+        i.add_data(0x0, &[0x00, 0x11, 0x22, 0x33]);
+        i.add_data(0x22, &[0x22, 0x33, 0x44, 0x55]);
+
+        assert_eq!(
+            i,
+            Image {
+                erased_value: UNPROGRAMMED_BYTE,
+                start_address: None,
+                regions: vec![
+                    Region {
+                        address_range: 0x0..=0xF,
+                        data: vec![
+                            0x00,
+                            0x11,
+                            0x22,
+                            0x33,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE
+                        ],
+                    },
+                    Region {
+                        address_range: 0x20..=0x2F,
+                        data: vec![
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            0x22,
+                            0x33,
+                            0x44,
+                            0x55,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE,
+                            UNPROGRAMMED_BYTE
+                        ],
+                    }
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn check_coverage_returns_uncovered_ranges() {
+        let i = Image::new(&[0x10..=0x1F, 0x20..=0x2F]);
+
+        let uncovered = i.check_coverage(&[
+            (0x0C, &[0x00; 8]),
+            (0x18, &[0x00; 16]),
+            (0x2E, &[0x00; 4]),
+            (0x40, &[0x00; 2]),
+        ]);
+
+        assert_eq!(uncovered, vec![0x0C..=0x0F, 0x30..=0x31, 0x40..=0x41]);
+    }
+
+    #[test]
+    fn check_coverage_reports_data_past_end_of_address_space() {
+        let i = Image::new(&[0xFFFF_FFF0..=0xFFFF_FFFD]);
+
+        let uncovered = i.check_coverage(&[(0xFFFF_FFFC, &[0x00; 8])]);
+
+        assert_eq!(uncovered, vec![0xFFFF_FFFE..=0xFFFF_FFFF]);
+    }
+
+    #[test]
+    fn programmable_blocks_empty_image_returns_empty_list() {
+        let i = Image::new(&[0x0..=0xF, 0x20..=0x2F]);
+
+        assert_eq!(i.programmable_blocks(0x2).count(), 0);
+    }
+
+    #[test]
+    fn programmable_blocks_returns_correct_blocks() {
+        let mut i = Image::new(&[0x0..=0xF, 0x20..=0x2F]);
+
+        i.add_data(0x0, &[0x00, 0x11, 0x22, 0x33]);
+        i.add_data(0x22, &[0x22, 0x33, 0x44, 0x55]);
+
+        let mut pb = i.programmable_blocks(0x4);
+        assert_eq!(
+            pb.next(),
+            Some(Block {
+                start_address: 0x0,
+                data: &[0x00, 0x11, 0x22, 0x33],
+                erased_value: UNPROGRAMMED_BYTE,
+            })
+        );
+        assert_eq!(
+            pb.next(),
+            Some(Block {
+                start_address: 0x20,
+                data: &[UNPROGRAMMED_BYTE, UNPROGRAMMED_BYTE, 0x22, 0x33],
+                erased_value: UNPROGRAMMED_BYTE,
+            })
+        );
+        assert_eq!(
+            pb.next(),
+            Some(Block {
+                start_address: 0x24,
+                data: &[0x44, 0x55, UNPROGRAMMED_BYTE, UNPROGRAMMED_BYTE],
+                erased_value: UNPROGRAMMED_BYTE,
+            })
+        );
+        assert_eq!(pb.next(), None);
+    }
+
+    #[test]
+    fn total_programmable_bytes_counts_populated_blocks() {
+        let mut i = Image::new(&[0x0..=0xF, 0x20..=0x2F]);
+
+        i.add_data(0x0, &[0x00, 0x11]);
+        i.add_data(0x26, &[0x22, 0x33, 0x44, 0x55]);
+
+        assert_eq!(i.total_programmable_bytes(0x4), 0xC);
+    }
+
+    #[test]
+    fn programmable_blocks_aligns_unaligned_data() {
+        let mut i = Image::new(&[0x100..=0x10F]);
+
+        i.add_data(0x106, &[0x11, 0x22, 0x33, 0x44]);
+
+        let mut pb = i.programmable_blocks(0x4);
+        assert_eq!(
+            pb.next(),
+            Some(Block {
+                start_address: 0x104,
+                data: &[UNPROGRAMMED_BYTE, UNPROGRAMMED_BYTE, 0x11, 0x22],
+                erased_value: UNPROGRAMMED_BYTE,
+            })
+        );
+        assert_eq!(
+            pb.next(),
+            Some(Block {
+                start_address: 0x108,
+                data: &[0x33, 0x44, UNPROGRAMMED_BYTE, UNPROGRAMMED_BYTE],
+                erased_value: UNPROGRAMMED_BYTE,
+            })
+        );
+        assert_eq!(pb.next(), None);
+    }
+
+    #[test]
+    fn programmable_blocks_uses_erased_value() {
+        let mut i = Image::with_erased_value(&[0x0..=0x7], 0x00);
+
+        i.add_data(0x4, &[0xFF, 0xFF, 0xFF, 0xFF]);
+
+        let mut pb = i.programmable_blocks(0x4);
+        assert_eq!(
+            pb.next(),
+            Some(Block {
+                start_address: 0x4,
+                data: &[0xFF, 0xFF, 0xFF, 0xFF],
+                erased_value: 0x00,
+            })
+        );
+        assert_eq!(pb.next(), None);
+    }
+
+    #[test]
+    fn programmable_block_count_matches_programmable_blocks() {
+        let mut i = Image::new(&[0x0..=0xF, 0x20..=0x2F]);
+
+        assert_eq!(i.programmable_block_count(0x4), 0);
+
+        i.add_data(0x0, &[0x00, 0x11, 0x22, 0x33]);
+        i.add_data(0x22, &[0x22, 0x33, 0x44, 0x55]);
+
+        assert_eq!(i.programmable_block_count(0x4), 3);
+        assert_eq!(
+            i.programmable_block_count(0x4),
+            i.programmable_blocks(0x4).count()
+        );
+    }
+
+    #[test]
+    fn populated_ranges_finds_gaps() {
+        let mut i = Image::new(&[0x20..=0x2F, 0x0..=0xF, 0x10..=0x1F]);
+
+        assert_eq!(i.populated_ranges(), vec![]);
+
+        i.add_data(0x2, &[0x00, 0x11]);
+        i.add_data(0x6, &[0x22, 0xFF, 0x33]);
+        i.add_data(0xE, &[0x44, 0x55]);
+        i.add_data(0x10, &[0x66]);
+        i.add_data(0x2F, &[0x77]);
+
+        assert_eq!(
+            i.populated_ranges(),
+            vec![0x2..=0x3, 0x6..=0x6, 0x8..=0x8, 0xE..=0x10, 0x2F..=0x2F]
+        );
+    }
+
+    #[test]
+    fn estimated_transfer_bytes_includes_command_overhead() {
+        let mut i = Image::new(&[0x0..=0xF, 0x20..=0x2F]);
+
+        assert_eq!(i.estimated_transfer_bytes(0x4), 7);
+
+        i.add_data(0x0, &[0x00, 0x11, 0x22, 0x33]);
+        i.add_data(0x22, &[0x22, 0x33, 0x44, 0x55]);
+
+        assert_eq!(i.estimated_transfer_bytes(0x4), 3 * 263 + 7);
+    }
+
+    #[test]
+    fn owned_programmable_blocks_matches_programmable_blocks() {
+        let mut i = Image::new(&[0x0..=0xF, 0x20..=0x2F]);
+        i.add_data(0x0, &[0x00, 0x11, 0x22, 0x33]);
+        i.add_data(0x22, &[0x22, 0x33, 0x44, 0x55]);
+
+        let blocks = i.owned_programmable_blocks(0x4);
+        drop(i);
+
+        assert_eq!(
+            blocks,
+            vec![
+                OwnedBlock {
+                    start_address: 0x0,
+                    data: vec![0x00, 0x11, 0x22, 0x33],
+                },
+                OwnedBlock {
+                    start_address: 0x20,
+                    data: vec![0xFF, 0xFF, 0x22, 0x33],
+                },
+                OwnedBlock {
+                    start_address: 0x24,
+                    data: vec![0x44, 0x55, 0xFF, 0xFF],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn block_matches_ignores_unpopulated_bytes() {
+        let block = Block {
+            start_address: 0x0,
+            data: &[UNPROGRAMMED_BYTE, 0x11, 0x22, UNPROGRAMMED_BYTE],
+            erased_value: UNPROGRAMMED_BYTE,
+        };
+
+        assert!(block.matches(&[0x00, 0x11, 0x22, 0x33]));
+        assert!(block.matches(&[UNPROGRAMMED_BYTE, 0x11, 0x22, UNPROGRAMMED_BYTE]));
+    }
+
+    #[test]
+    fn block_matches_detects_mismatched_populated_bytes() {
+        let block = Block {
+            start_address: 0x0,
+            data: &[UNPROGRAMMED_BYTE, 0x11, 0x22, UNPROGRAMMED_BYTE],
+            erased_value: UNPROGRAMMED_BYTE,
+        };
+
+        assert!(!block.matches(&[UNPROGRAMMED_BYTE, 0x11, 0x23, UNPROGRAMMED_BYTE]));
+        assert!(!block.matches(&[UNPROGRAMMED_BYTE, 0x11, 0x22]));
+    }
+
+    #[test]
+    fn merge_combines_images() {
+        let mut i = Image::new(&[0x0..=0x3]);
+        let mut j = Image::new(&[0x0..=0x3]);
+
+        i.add_data(0x0, &[0x00, 0x11]);
+        j.add_data(0x2, &[0x22, 0x33]);
+
+        assert_eq!(i.merge(&j), Ok(()));
+        assert_eq!(
+            i,
+            Image {
+                erased_value: UNPROGRAMMED_BYTE,
+                start_address: None,
+                regions: vec![Region {
+                    address_range: 0x0..=0x3,
+                    data: vec![0x00, 0x11, 0x22, 0x33],
+                }]
+            }
+        );
+    }
+
+    #[test]
+    fn merge_rejects_overlapping_images() {
+        let mut i = Image::new(&[0x0..=0x3]);
+        let mut j = Image::new(&[0x0..=0x3]);
+
+        i.add_data(0x0, &[0x00, 0x11]);
+        j.add_data(0x1, &[0x22, 0x33]);
+
+        assert_eq!(i.merge(&j), Err(0x1));
+        assert_eq!(
+            i,
+            Image {
+                erased_value: UNPROGRAMMED_BYTE,
+                start_address: None,
+                regions: vec![Region {
+                    address_range: 0x0..=0x3,
+                    data: vec![0x00, 0x11, UNPROGRAMMED_BYTE, UNPROGRAMMED_BYTE],
+                }]
+            }
+        );
+    }
+
+    #[test]
+    fn memory_map_string_describes_regions() {
+        let mut i = Image::new(&[0x0..=0xF, 0xFFFFFF00..=0xFFFFFFFF]);
+
+        i.add_data(0x2, &[0x22, 0x33, 0x44]);
+
+        assert_eq!(
+            i.memory_map_string(),
+            "0x00000000-0x0000000F: 3 bytes populated, 13 bytes fill\n\
+             0xFFFFFF00-0xFFFFFFFF: 0 bytes populated, 256 bytes fill\n"
+        );
+    }
+
+    #[test]
+    fn split_by_areas_partitions_data() {
+        let mut i = Image::new(&[0x0..=0x7, 0x20..=0x23]);
+
+        i.add_data(0x2, &[0x22, 0x33, 0x44, 0x55]);
+        i.add_data(0x20, &[0x88, 0x99]);
+
+        let (user_boot, user) = i.split_by_areas(&[0x0..=0x3], &[0x4..=0x7, 0x20..=0x23]);
+
+        assert_eq!(user_boot.regions.len(), 1);
+        assert_eq!(user_boot.regions[0].data, [0xFF, 0xFF, 0x22, 0x33]);
+        assert_eq!(user.regions.len(), 2);
+        assert_eq!(user.regions[0].data, [0x44, 0x55, 0xFF, 0xFF]);
+        assert_eq!(user.regions[1].data, [0x88, 0x99, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn mask_to_clears_bytes_outside_ranges() {
+        let mut i = Image::new(&[0x0..=0x7, 0x20..=0x23]);
+
+        i.add_data(0x0, &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77]);
+        i.add_data(0x20, &[0x88, 0x99, 0xAA, 0xBB]);
+        i.mask_to(&[0x2..=0x3, 0x6..=0x21]);
+
+        assert_eq!(
+            i.regions[0].data,
+            [0xFF, 0xFF, 0x22, 0x33, 0xFF, 0xFF, 0x66, 0x77]
+        );
+        assert_eq!(i.regions[1].data, [0x88, 0x99, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn checksum_sums_all_bytes() {
+        let mut i = Image::new(&[0x0..=0x3, 0x20..=0x21]);
+
+        i.add_data(0x0, &[0x00, 0x11, 0x22, 0x33]);
+        i.add_data(0x20, &[0x44]);
+
+        assert_eq!(i.checksum(), 0x00 + 0x11 + 0x22 + 0x33 + 0x44 + 0xFF);
+    }
+
+    #[test]
+    fn user_boot_area_checksum_matches_known_vectors() {
+        let mut i = Image::new(&[0xFF7FC000..=0xFF7FFFFF]);
+
+        assert_eq!(i.user_boot_area_checksum(), 0x003FC000);
+
+        i.add_data(0xFF7FC000, &[0x00, 0x01, 0x02, 0x03]);
+
+        assert_eq!(i.user_boot_area_checksum(), 0x003FC000 - 4 * 0xFF + 0x06);
+    }
+
+    #[test]
+    #[cfg(feature = "srec")]
+    fn add_data_from_srec_captures_start_address() {
+        let mut i = Image::new(&[0x1000..=0x100F]);
+
+        let records = vec![
+            Ok(srec::Record::S1(srec::Data {
+                address: srec::Address16(0x1000),
+                data: vec![0x12, 0x34],
+            })),
+            Ok(srec::Record::S9(srec::Address16(0x1000))),
+        ];
+        i.add_data_from_srec(records.into_iter()).unwrap();
+
+        assert_eq!(i.start_address(), Some(0x1000));
+    }
+
+    #[test]
+    #[cfg(feature = "ihex")]
+    fn add_data_from_ihex_handles_extended_segment_address() {
+        let mut i = Image::new(&[0x10000..=0x1000F]);
+
+        let reader = ihex::Reader::new(":020000021000EC\n:02000400ABCD82\n:00000001FF");
+        assert_eq!(i.add_data_from_ihex(reader).unwrap(), vec![]);
+
+        let mut data = vec![0xFF; 0x10];
+        data[0x4] = 0xAB;
+        data[0x5] = 0xCD;
+        assert_eq!(i.regions[0].data, data);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ihex", feature = "srec"))]
+    fn from_path_detects_format() {
+        let path = std::env::temp_dir().join(format!("rxprog-{}.hex", std::process::id()));
+        std::fs::write(&path, ":020000001234B8\n:00000001FF").unwrap();
+
+        let i = Image::from_path(&path, &[0x0..=0xF]);
+        std::fs::remove_file(&path).unwrap();
+
+        let mut expected = Image::new(&[0x0..=0xF]);
+        expected.add_data(0x0, &[0x12, 0x34]);
+        assert_eq!(i, Ok(expected));
+    }
+
+    #[test]
+    #[cfg(feature = "ihex")]
+    fn add_data_from_ihex_skips_and_returns_uncovered_data() {
+        let mut i = Image::new(&[0x0..=0x3]);
+
+        let reader =
+            ihex::Reader::new(":040002001122334450\n:0200060055663D\n:020010007788EF\n:00000001FF");
+        assert_eq!(
+            i.add_data_from_ihex(reader).unwrap(),
+            vec![0x4..=0x7, 0x10..=0x11]
+        );
+
+        assert_eq!(i.regions[0].data, vec![0xFF, 0xFF, 0x11, 0x22]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ihex", feature = "srec"))]
+    fn from_path_rejects_uncovered_data() {
+        let path =
+            std::env::temp_dir().join(format!("rxprog-uncovered-{}.hex", std::process::id()));
+        std::fs::write(&path, ":020010007788EF\n:00000001FF").unwrap();
+
+        let i = Image::from_path(&path, &[0x0..=0xF]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(i.map_err(|e| e.kind), Err(crate::ErrorKind::Argument));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ihex", feature = "srec"))]
+    fn from_path_rejects_unknown_extension() {
+        let i = Image::from_path(Path::new("image.bin"), &[0x0..=0xF]);
+
+        assert_eq!(i.map_err(|e| e.kind), Err(crate::ErrorKind::Argument));
+    }
+}
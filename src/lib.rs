@@ -14,9 +14,19 @@ pub mod command;
 
 /// Connection to a target device
 pub mod target;
+pub use target::available_targets;
 
 /// Interface wrapping a serial port to program a device
 pub mod programmer;
+pub use programmer::identify;
+
+/// Firmware images to be programmed into a device
+pub mod image;
+
+/// Replaying recorded device transcripts, for testing programmer sequences
+/// without hardware
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
 /// A type for results generated when communicating with/programming a target
 /// device
@@ -24,7 +34,7 @@ pub type Result<T> = result::Result<T, Error>;
 
 /// Categories of errors that can occur when communicating with/programming a
 /// target device
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ErrorKind {
     /// A connection to the target could not be established
     Connect,
@@ -32,10 +42,14 @@ pub enum ErrorKind {
     Command(command::CommandError),
     /// An I/O error occurred
     Io(io::ErrorKind),
+    /// An invalid argument was passed to a library function
+    Argument,
+    /// The target sent a response which could not be interpreted
+    Protocol,
 }
 
 /// An error type for communication/programming operations
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Error {
     /// The kind of error that occurred
     pub kind: ErrorKind,
@@ -1,8 +1,16 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
 use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time;
 
+use log::debug;
+
 use crate::command::{self, Command};
+use crate::image::Image;
 use crate::target::{OperatingMode, Target};
 use crate::{Error, ErrorKind, Result};
 
@@ -17,32 +25,268 @@ pub enum ConnectError {
     Failed,
 }
 
+/// Options controlling how a connection to a device is established
+#[derive(Clone, Debug, Default)]
+pub struct ConnectConfig {
+    /// Time to wait after resetting the device before attempting to synchronise, for boards on
+    /// which the boot program is slow to start
+    pub settle_delay: time::Duration,
+    /// When set to `true` (e.g. from another thread), the connection attempt is abandoned and
+    /// `connect` returns a `Connect` error
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// How the synchronisation confirmation and device and clock mode selection commands are
+    /// retried
+    pub retry_policy: RetryPolicy,
+    /// Skips resetting the target into boot mode, for when the device has already been started in
+    /// boot mode (e.g. powered up manually)
+    pub skip_reset: bool,
+    /// Read timeouts applied to the target once connected. If `None`, the target's timeout is
+    /// left unchanged.
+    pub timeouts: Option<Timeouts>,
+    /// Baud rate to synchronise with the device at. If `None`, 9600, 4800, 2400 and 1200 bps are
+    /// tried in turn until the device responds.
+    pub fixed_baud_rate: Option<u32>,
+    /// Confirms the device advanced to the expected state after device, clock mode and bit rate
+    /// selection by requesting the boot program's status. Those commands are acknowledged with a
+    /// single byte, which a device that has lost synchronisation can appear to send.
+    pub confirm_transitions: bool,
+    /// Byte order of the operating frequencies reported by the device. The Boot Mode protocol
+    /// documents them as big-endian, but some devices send them little-endian.
+    pub frequency_endianness: command::data::Endianness,
+}
+
+/// How operations which fail transiently are retried. Between attempts, the target's buffers are
+/// cleared to discard any partial response.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first. `1` disables retrying.
+    pub max_attempts: u32,
+    /// Time to wait before the first retry
+    pub base_delay: time::Duration,
+    /// Factor the delay is multiplied by after each retry
+    pub backoff_multiplier: u32,
+    /// Kinds of error which are retried. Errors of any other kind are returned immediately.
+    pub retryable: Vec<ErrorKind>,
+}
+
+impl RetryPolicy {
+    /// A policy which never retries
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Returns true if the policy retries errors of the same kind as `error`
+    pub fn is_retryable(&self, error: &Error) -> bool {
+        self.retryable.contains(&error.kind)
+    }
+
+    fn run<T>(
+        &self,
+        target: &mut Box<dyn Target>,
+        mut operation: impl FnMut(&mut Box<dyn Target>) -> Result<T>,
+    ) -> Result<T> {
+        let mut attempt = 1;
+        let mut delay = self.base_delay;
+        loop {
+            match operation(target) {
+                Err(error) if attempt < self.max_attempts && self.is_retryable(&error) => {
+                    target.clear_buffers()?;
+                    thread::sleep(delay);
+
+                    attempt += 1;
+                    delay = self.next_delay(delay);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // The delay before the retry after one which waited `delay`, saturating rather than
+    // overflowing
+    fn next_delay(&self, delay: time::Duration) -> time::Duration {
+        delay
+            .checked_mul(self.backoff_multiplier)
+            .unwrap_or(time::Duration::MAX)
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Up to 3 attempts without delay, retrying timeouts and malformed responses. Errors reported
+    /// by the device are not retried.
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: time::Duration::from_millis(0),
+            backoff_multiplier: 2,
+            retryable: vec![ErrorKind::Io(io::ErrorKind::TimedOut), ErrorKind::Protocol],
+        }
+    }
+}
+
+/// Read timeouts for the different phases of a session
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timeouts {
+    /// Timeout for responses to ordinary commands, which the device answers almost immediately
+    pub command: time::Duration,
+    /// Timeout for responses to operations which take the device some time to complete, such as
+    /// erasing blocks, blank checks and calculating checksums
+    pub long_operation: time::Duration,
+}
+
+impl ConnectConfig {
+    fn is_cancelled(&self) -> bool {
+        match &self.cancel {
+            Some(cancel) => cancel.load(Ordering::SeqCst),
+            None => false,
+        }
+    }
+}
+
+/// Everything required to negotiate a connection with a device, as passed to
+/// `Programmer::negotiate`
+#[derive(Clone, Debug, PartialEq)]
+pub struct NegotiationParams {
+    /// Device code of the device to select
+    pub device_code: command::data::DeviceCode,
+    /// Clock mode to select
+    pub clock_mode: u8,
+    /// New bit rate, in units of 100 bps
+    pub bit_rate: u16,
+    /// Frequency of the device's input clock
+    pub input_frequency: command::data::InputFrequency,
+    /// Multiplication ratio for each of the device's clocks
+    pub multiplication_ratios: Vec<command::data::MultiplicationRatio>,
+}
+
 /// A programmer connected to a device, through a serial port
 pub struct Programmer {
     target: Box<dyn Target>,
+    config: ConnectConfig,
 }
 
 impl Programmer {
     /// Creates a new programmer connected to the provided serial port
     pub fn new(target: Box<dyn Target>) -> Programmer {
-        Programmer { target }
+        Programmer::with_config(target, ConnectConfig::default())
+    }
+
+    /// Creates a new programmer connected to the provided serial port, which connects using the
+    /// specified options
+    pub fn with_config(target: Box<dyn Target>, config: ConnectConfig) -> Programmer {
+        Programmer { target, config }
+    }
+
+    /// Resets the device back into boot mode and reconnects to it, e.g. after a command fails
+    /// mid-session. The target can be retrieved from a programmer with `into_target`.
+    pub fn recover(target: Box<dyn Target>) -> Result<ProgrammerConnected> {
+        Programmer::recover_with_config(target, ConnectConfig::default())
+    }
+
+    /// Like `recover`, but connects using the specified options. The device is always reset,
+    /// regardless of `skip_reset`.
+    pub fn recover_with_config(
+        target: Box<dyn Target>,
+        config: ConnectConfig,
+    ) -> Result<ProgrammerConnected> {
+        let config = ConnectConfig {
+            skip_reset: false,
+            ..config
+        };
+
+        Programmer::with_config(target, config).connect()
     }
 
     /// Attempts to make an initial connection to the device
+    ///
+    /// If the device is still connected from an earlier session, it treats the synchronisation
+    /// bytes as commands and responds with errors. In that case the boot program's status is
+    /// requested: a device still waiting for device selection is adopted as-is, and any other
+    /// device is reset and synchronised again (unless resets are skipped).
     pub fn connect(mut self) -> Result<ProgrammerConnected> {
-        self.target.reset_into(OperatingMode::Boot);
+        if !self.config.skip_reset {
+            self.reset()?;
+        }
+
+        if self.synchronise()? {
+            return self.into_connected();
+        }
+
+        let cmd = command::commands::BootProgramStatusInquiry {};
+        let status = cmd
+            .execute(&mut self.target)
+            .map(|response| response.status);
+
+        match status {
+            Ok(command::commands::BootProgramStatus::WaitingForDeviceSelection) => {
+                self.into_connected()
+            }
+            Ok(_) if !self.config.skip_reset => {
+                self.reset()?;
+
+                if self.synchronise()? {
+                    self.into_connected()
+                } else {
+                    Err(Error::new(ErrorKind::Connect, "bad response from target"))
+                }
+            }
+            Ok(status) => Err(Error::new(
+                ErrorKind::Connect,
+                format!("target is already connected ({:?})", status),
+            )),
+            Err(_) => Err(Error::new(ErrorKind::Connect, "bad response from target")),
+        }
+    }
 
+    /// Connects to the device, then selects the device, clock mode and new bit rate, leaving the
+    /// programmer ready for area inquiries or the transition to the programming/erasure state
+    pub fn negotiate(
+        self,
+        params: NegotiationParams,
+    ) -> Result<ProgrammerConnectedNewBitRateSelected> {
+        self.connect()?
+            .select_device(&params.device_code)?
+            .select_clock_mode(params.clock_mode)?
+            .set_new_bit_rate(
+                params.bit_rate,
+                params.input_frequency,
+                params.multiplication_ratios,
+            )
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.target.reset_into(OperatingMode::Boot)?;
+
+        thread::sleep(self.config.settle_delay);
+
+        Ok(())
+    }
+
+    // Performs the 0x00/0x55 synchronisation sequence. Returns false if the device responded to
+    // the 0x00 bytes with something else, as it does when it's already synchronised.
+    fn synchronise(&mut self) -> Result<bool> {
         self.target.clear_buffers()?;
 
-        for baud_rate in &[9600, 4800, 2400, 1200, 0] {
-            if *baud_rate == 0 {
+        let baud_rates = match self.config.fixed_baud_rate {
+            Some(baud_rate) => vec![baud_rate, 0],
+            None => vec![9600, 4800, 2400, 1200, 0],
+        };
+
+        for baud_rate in baud_rates {
+            if baud_rate == 0 {
                 return Err(Error::new(ErrorKind::Connect, "no response from target"));
             }
 
-            self.target.set_baud_rate(*baud_rate)?;
+            self.target.set_baud_rate(baud_rate)?;
 
             let mut attempts = 0;
             while self.target.bytes_to_read()? < 1 && attempts < 30 {
+                if self.config.is_cancelled() {
+                    return Err(Error::new(ErrorKind::Connect, "cancelled"));
+                }
+
                 self.target.write(&[0x00])?;
                 thread::sleep(time::Duration::from_millis(10));
 
@@ -59,49 +303,440 @@ impl Programmer {
         let response1 = response1[0];
 
         if response1 != 0x00 {
-            return Err(Error::new(ErrorKind::Connect, "bad response from target"));
+            // Discard the rest of the error responses before the caller talks to the device
+            thread::sleep(time::Duration::from_millis(10));
+            self.target.clear_buffers()?;
+
+            return Ok(false);
+        }
+
+        let result = self.config.retry_policy.run(&mut self.target, |target| {
+            target.write(&[0x55])?;
+
+            let mut response2 = [0u8; 1];
+            target.read_exact(&mut response2)?;
+
+            match response2[0] {
+                0xE6 => Ok(true),
+                0xFF => Err(Error::new(ErrorKind::Connect, "failed to connect")),
+                _ => Err(Error::new(ErrorKind::Protocol, "bad confirmation response")),
+            }
+        });
+
+        match result {
+            Err(Error {
+                kind: ErrorKind::Protocol,
+                ..
+            }) => Err(Error::new(ErrorKind::Connect, "bad response from target")),
+            result => result,
+        }
+    }
+
+    fn into_connected(mut self) -> Result<ProgrammerConnected> {
+        if let Some(timeouts) = self.config.timeouts {
+            self.target.set_timeout(timeouts.command)?;
+        }
+
+        debug!("Programmer -> ProgrammerConnected");
+
+        Ok(ProgrammerConnected {
+            target: self.target,
+            config: self.config,
+        })
+    }
+}
+
+/// Checks the device reports `expected` as its status, if transitions are being confirmed
+fn confirm_status(
+    target: &mut Box<dyn Target>,
+    config: &ConnectConfig,
+    expected: command::commands::BootProgramStatus,
+) -> Result<()> {
+    if !config.confirm_transitions {
+        return Ok(());
+    }
+
+    check_status(target, expected)
+}
+
+/// Checks the device reports `expected` as its status
+fn check_status(
+    target: &mut Box<dyn Target>,
+    expected: command::commands::BootProgramStatus,
+) -> Result<()> {
+    let cmd = command::commands::BootProgramStatusInquiry {};
+    let status = cmd.execute(target)?.status;
+    if status != expected {
+        return Err(Error::new(
+            ErrorKind::Protocol,
+            format!("device status is {:?}, expected {:?}", status, expected),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Executes a command which takes the device some time to complete, under the long operation
+/// timeout
+fn execute_long_operation<T: Command>(
+    target: &mut Box<dyn Target>,
+    cmd: &T,
+    timeouts: Option<Timeouts>,
+) -> Result<T::Response> {
+    cmd.execute_with_timeout(
+        &mut **target,
+        timeouts.map(|timeouts| timeouts.long_operation),
+    )
+}
+
+/// Updates a CRC-32 (IEEE 802.3, reflected) with `data`
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
         }
+    }
+
+    crc
+}
+
+/// Bytes per second at a baud rate, with each byte framed by a start and stop bit
+fn byte_rate(baud_rate: u32) -> u32 {
+    baud_rate / 10
+}
+
+/// Returns the result of an inquiry made before the transition to the programming/erasure state,
+/// or an error naming the method to call if it wasn't made
+fn inquired<'a, T>(result: &'a Option<T>, what: &str, inquiry: &str) -> Result<&'a T> {
+    result.as_ref().ok_or_else(|| {
+        Error::new(
+            ErrorKind::Argument,
+            format!(
+                "{} unknown: call `{}` before transitioning to the programming/erasure state",
+                what, inquiry
+            ),
+        )
+    })
+}
+
+/// Returns the size of the blocks images are programmed in. Only the 256 byte programming command
+/// is implemented, so a device which reported a different programming unit size can't be
+/// programmed.
+fn programming_block_size(block_size: Option<u16>) -> Result<usize> {
+    match block_size {
+        None | Some(256) => Ok(256),
+        Some(block_size) => Err(Error::new(
+            ErrorKind::Protocol,
+            format!(
+                "{} byte programming unit is not supported, only 256 byte programming is \
+                 implemented",
+                block_size
+            ),
+        )),
+    }
+}
+
+/// Checks that `size` bytes starting at `start_address` lie within one of an area's regions
+fn check_read_bounds(
+    regions: &[RangeInclusive<u32>],
+    area_name: &str,
+    start_address: u32,
+    size: u32,
+) -> Result<()> {
+    let end_address = start_address.checked_add(size.saturating_sub(1));
+    let in_bounds = end_address.is_some_and(|end_address| {
+        regions
+            .iter()
+            .any(|region| region.contains(&start_address) && region.contains(&end_address))
+    });
+    if in_bounds {
+        return Ok(());
+    }
+
+    let bounds = regions
+        .iter()
+        .map(|region| format!("{:#X}-{:#X}", region.start(), region.end()))
+        .collect::<Vec<_>>();
+
+    Err(Error::new(
+        ErrorKind::Argument,
+        format!(
+            "{} bytes at {:#X} is outside the {} ({})",
+            size,
+            start_address,
+            area_name,
+            if bounds.is_empty() {
+                "empty".to_string()
+            } else {
+                bounds.join(", ")
+            }
+        ),
+    ))
+}
+
+/// Converts an index into the erasure block list into a `BlockErasure` block number. Block numbers
+/// are a single byte, and 0xFF ends block erasure rather than erasing a block.
+fn erasure_block_number(index: usize) -> Result<u8> {
+    match u8::try_from(index) {
+        Ok(block_number) if block_number != 0xFF => Ok(block_number),
+        _ => Err(Error::new(
+            ErrorKind::Argument,
+            format!("erasure block {} can't be addressed", index),
+        )),
+    }
+}
+
+/// Adds the location of the block being programmed to an error
+fn block_error(error: Error, address: u32, length: usize) -> Error {
+    Error::new(
+        error.kind,
+        format!(
+            "programming failed at {:#010X} ({} bytes): {}",
+            address, length, error.description
+        ),
+    )
+}
+
+/// Sends the end of programming command, returning the device to the programming/erasure state.
+/// The command is sent even if programming has already failed, so the device isn't left waiting
+/// for data, but the programming error takes precedence over any error ending programming.
+fn end_programming(target: &mut Box<dyn Target>, result: Result<()>) -> Result<()> {
+    let cmd = command::commands::X256ByteProgramming {
+        address: 0xFFFFFFFF,
+        data: [0u8; 256],
+    };
+    let end_result = cmd.execute(target);
+
+    result.and(end_result)
+}
+
+/// How a programmed image is checked against the contents of the device
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VerifyMode {
+    /// Skip verification entirely
+    None,
+    /// Compare the user area checksum calculated by the device with the image's checksum
+    Checksum,
+    /// Read back every programmed block and compare it with the image
+    FullReadback,
+}
+
+/// The outcome of programming or verifying an image
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlashReport {
+    /// The verification performed
+    pub verify_mode: VerifyMode,
+    /// Whether the device contents were verified to match the image. Always `false` when
+    /// verification was skipped.
+    pub verified: bool,
+    /// Start addresses of blocks which did not match the image. Only populated by
+    /// `VerifyMode::FullReadback`.
+    pub mismatched_blocks: Vec<u32>,
+    /// The outcome of each block programmed or read back
+    pub block_results: Vec<BlockResult>,
+    /// The checksums compared, only populated by `VerifyMode::Checksum`
+    pub checksums: Option<ChecksumComparison>,
+}
+
+/// The checksums compared when verifying an image with `VerifyMode::Checksum`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChecksumComparison {
+    /// Checksum of the user area calculated from the image
+    pub image: u32,
+    /// Checksum of the user area reported by the device
+    pub device: u32,
+}
+
+/// The outcome of programming or reading back a single block of an image
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockResult {
+    /// Start address of the block
+    pub address: u32,
+    /// Length of the block in bytes
+    pub length: usize,
+    /// The error encountered programming or reading back the block, if any
+    pub programming_error: Option<Error>,
+    /// Whether the block matched the image when read back, or `None` if it wasn't read back
+    pub matched: Option<bool>,
+}
+
+/// Information gathered at the end of a programming session
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionSummary {
+    /// Checksum of the user boot area, if requested
+    pub user_boot_area_checksum: Option<u32>,
+    /// Checksum of the user area, if requested
+    pub user_area_checksum: Option<u32>,
+}
+
+/// The regions comprising one of the device's memory areas, as reported by the device
+#[derive(Clone, Debug, PartialEq)]
+pub struct AreaMap {
+    /// Human-readable name of the area, e.g. "User area"
+    pub label: &'static str,
+    /// The area's regions, in the order reported by the device. A region's index is its position
+    /// in this list.
+    pub regions: Vec<RangeInclusive<u32>>,
+}
+
+impl AreaMap {
+    /// Size of a single region in bytes
+    pub fn region_size(region: &RangeInclusive<u32>) -> u64 {
+        (*region.end() as u64) - (*region.start() as u64) + 1
+    }
+
+    /// Total size of all of the area's regions in bytes
+    pub fn size(&self) -> u64 {
+        self.regions.iter().map(AreaMap::region_size).sum()
+    }
+}
 
-        self.target.write(&[0x55])?;
+impl fmt::Display for AreaMap {
+    /// Describes each region of the area, one region per line, e.g.
+    /// `User area: 0xFFF00000-0xFFFFFFFF (1 MiB)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, region) in self.regions.iter().enumerate() {
+            if self.regions.len() == 1 {
+                write!(f, "{}: ", self.label)?;
+            } else {
+                write!(f, "{} {}: ", self.label, index)?;
+            }
 
-        let mut response2 = [0u8; 1];
-        self.target.read_exact(&mut response2)?;
-        let response2 = response2[0];
+            let size = AreaMap::region_size(region);
+            let size = if size % (1 << 20) == 0 {
+                format!("{} MiB", size >> 20)
+            } else if size % (1 << 10) == 0 {
+                format!("{} KiB", size >> 10)
+            } else {
+                format!("{} bytes", size)
+            };
 
-        match response2 {
-            0xE6 => Ok(ProgrammerConnected {
-                target: self.target,
-            }),
-            0xFF => Err(Error::new(ErrorKind::Connect, "failed to connect")),
-            _ => Err(Error::new(ErrorKind::Connect, "bad response from target")),
+            writeln!(
+                f,
+                "{:#010X}-{:#010X} ({})",
+                region.start(),
+                region.end(),
+                size
+            )?;
         }
+
+        Ok(())
     }
 }
 
+/// Connects to the device on `target` and returns the devices it supports, without selecting
+/// one. The programmer (and with it the target) is dropped before returning, so this can be
+/// used to find out what's connected to each of several ports in turn.
+pub fn identify(target: Box<dyn Target>) -> Result<Vec<command::data::SupportedDevice>> {
+    let mut prog = Programmer::new(target).connect()?;
+    let devices = prog.supported_devices()?;
+
+    debug!("Identified {} supported devices", devices.len());
+
+    Ok(devices)
+}
+
 /// A programmer connected to a device
 pub struct ProgrammerConnected {
     target: Box<dyn Target>,
+    config: ConnectConfig,
 }
 
 impl ProgrammerConnected {
-    /// Retrieve a list of devices supported by the target
+    /// Creates a programmer in this state without communicating with the device, for testing code
+    /// which operates on this state against a mock target. The device must already be in the
+    /// corresponding state for subsequent commands to succeed.
+    #[cfg(feature = "test-util")]
+    pub fn from_target(target: Box<dyn Target>, config: ConnectConfig) -> ProgrammerConnected {
+        ProgrammerConnected { target, config }
+    }
+
+    /// Executes a command, returning the raw bytes of the device's response alongside the parsed
+    /// response, for debugging unexpected inquiry results
+    pub fn execute_raw<C: Command>(&mut self, cmd: &C) -> Result<(C::Response, Vec<u8>)> {
+        cmd.execute_raw(&mut self.target)
+    }
+
+    /// Retrieve a list of devices supported by the target. Doesn't change the programmer's state,
+    /// so can be called any number of times.
     pub fn supported_devices(&mut self) -> Result<Vec<command::data::SupportedDevice>> {
         let cmd = command::commands::SupportedDeviceInquiry {};
         cmd.execute(&mut self.target)
     }
 
+    /// Like `supported_devices`, but sorted by device code, with only the first of any devices
+    /// sharing a device code kept
+    pub fn unique_supported_devices(&mut self) -> Result<Vec<command::data::SupportedDevice>> {
+        let mut devices = self.supported_devices()?;
+
+        // The sort is stable, so the first device listed with each code is kept
+        devices.sort_by(|a, b| a.device_code.cmp(&b.device_code));
+        devices.dedup_by(|a, b| a.device_code == b.device_code);
+
+        Ok(devices)
+    }
+
+    /// Retrieves the current status of the boot program and the last error it encountered
+    ///
+    /// The Boot Mode protocol has no command to read the boot program's version, so this is the
+    /// only information the boot program reports about itself.
+    pub fn boot_program_status(
+        &mut self,
+    ) -> Result<command::commands::BootProgramStatusInquiryResponse> {
+        let cmd = command::commands::BootProgramStatusInquiry {};
+        cmd.execute(&mut self.target)
+    }
+
+    /// Checks whether a device is in the target's list of supported devices
+    pub fn supports_device(&mut self, device_code: &str) -> Result<bool> {
+        Ok(self
+            .supported_devices()?
+            .iter()
+            .any(|device| device.device_code.as_str() == device_code))
+    }
+
+    /// Retrieves the only device supported by the target, or `None` if the target supports more
+    /// than one device
+    pub fn only_supported_device(&mut self) -> Result<Option<command::data::SupportedDevice>> {
+        let mut devices = self.supported_devices()?;
+
+        Ok(if devices.len() == 1 {
+            devices.pop()
+        } else {
+            None
+        })
+    }
+
     /// Selects a device
     pub fn select_device(
         mut self,
-        device_code: &String,
+        device_code: &command::data::DeviceCode,
     ) -> Result<ProgrammerConnectedDeviceSelected> {
         let cmd = command::commands::DeviceSelection {
             device_code: device_code.clone(),
         };
-        cmd.execute(&mut self.target)?;
+        self.config
+            .retry_policy
+            .run(&mut self.target, |target| cmd.execute(target))?;
+        confirm_status(
+            &mut self.target,
+            &self.config,
+            command::commands::BootProgramStatus::WaitingForClockModeSelection,
+        )?;
+
+        debug!(
+            "ProgrammerConnected -> DeviceSelected (device code {})",
+            device_code
+        );
 
         Ok(ProgrammerConnectedDeviceSelected {
             target: self.target,
+            config: self.config,
         })
     }
 }
@@ -109,25 +744,70 @@ impl ProgrammerConnected {
 /// A programmer connected to a device, with a device selected
 pub struct ProgrammerConnectedDeviceSelected {
     target: Box<dyn Target>,
+    config: ConnectConfig,
 }
 
 impl ProgrammerConnectedDeviceSelected {
-    /// Retrieve a list of supported clock modes
+    /// Creates a programmer in this state without communicating with the device, for testing code
+    /// which operates on this state against a mock target. The device must already be in the
+    /// corresponding state for subsequent commands to succeed.
+    #[cfg(feature = "test-util")]
+    pub fn from_target(
+        target: Box<dyn Target>,
+        config: ConnectConfig,
+    ) -> ProgrammerConnectedDeviceSelected {
+        ProgrammerConnectedDeviceSelected { target, config }
+    }
+
+    /// Executes a command, returning the raw bytes of the device's response alongside the parsed
+    /// response, for debugging unexpected inquiry results
+    pub fn execute_raw<C: Command>(&mut self, cmd: &C) -> Result<(C::Response, Vec<u8>)> {
+        cmd.execute_raw(&mut self.target)
+    }
+
+    /// Retrieve a list of supported clock modes. Doesn't change the programmer's state, so can be
+    /// called any number of times.
     pub fn clock_modes(&mut self) -> Result<Vec<u8>> {
         let cmd = command::commands::ClockModeInquiry {};
         cmd.execute(&mut self.target)
     }
 
+    /// Retrieves the only clock mode supported by the device, or `None` if the device supports
+    /// more than one clock mode
+    pub fn only_clock_mode(&mut self) -> Result<Option<u8>> {
+        let clock_modes = self.clock_modes()?;
+
+        Ok(if clock_modes.len() == 1 {
+            Some(clock_modes[0])
+        } else {
+            None
+        })
+    }
+
     /// Selects a clock mode
     pub fn select_clock_mode(
         mut self,
         clock_mode: u8,
     ) -> Result<ProgrammerConnectedClockModeSelected> {
         let cmd = command::commands::ClockModeSelection { mode: clock_mode };
-        cmd.execute(&mut self.target)?;
+        self.config
+            .retry_policy
+            .run(&mut self.target, |target| cmd.execute(target))?;
+        confirm_status(
+            &mut self.target,
+            &self.config,
+            command::commands::BootProgramStatus::WaitingForBitRateSelection,
+        )?;
+
+        debug!(
+            "DeviceSelected -> ClockModeSelected (clock mode {})",
+            clock_mode
+        );
 
         Ok(ProgrammerConnectedClockModeSelected {
             target: self.target,
+            config: self.config,
+            clock_mode,
         })
     }
 }
@@ -135,10 +815,49 @@ impl ProgrammerConnectedDeviceSelected {
 /// A programmer connected to a device, with a clock mode selected
 pub struct ProgrammerConnectedClockModeSelected {
     target: Box<dyn Target>,
+    config: ConnectConfig,
+    clock_mode: u8,
 }
 
 impl ProgrammerConnectedClockModeSelected {
-    /// Retrieve a list of multiplication ratios supported by each clock
+    /// Creates a programmer in this state without communicating with the device, for testing code
+    /// which operates on this state against a mock target. The device must already be in the
+    /// corresponding state for subsequent commands to succeed.
+    #[cfg(feature = "test-util")]
+    pub fn from_target(
+        target: Box<dyn Target>,
+        config: ConnectConfig,
+        clock_mode: u8,
+    ) -> ProgrammerConnectedClockModeSelected {
+        ProgrammerConnectedClockModeSelected {
+            target,
+            config,
+            clock_mode,
+        }
+    }
+
+    /// Executes a command, returning the raw bytes of the device's response alongside the parsed
+    /// response, for debugging unexpected inquiry results
+    pub fn execute_raw<C: Command>(&mut self, cmd: &C) -> Result<(C::Response, Vec<u8>)> {
+        cmd.execute_raw(&mut self.target)
+    }
+
+    /// Returns the selected clock mode, after confirming the device accepted the selection
+    ///
+    /// The Boot Mode protocol has no command to read back the selected clock mode, so the device's
+    /// status is requested instead: a device which is waiting for bit rate selection has accepted
+    /// the clock mode it was sent.
+    pub fn current_clock_mode(&mut self) -> Result<u8> {
+        check_status(
+            &mut self.target,
+            command::commands::BootProgramStatus::WaitingForBitRateSelection,
+        )?;
+
+        Ok(self.clock_mode)
+    }
+
+    /// Retrieve a list of multiplication ratios supported by each clock. Doesn't change the
+    /// programmer's state, so can be called any number of times.
     pub fn multiplication_ratios(
         &mut self,
     ) -> Result<Vec<Vec<command::data::MultiplicationRatio>>> {
@@ -146,22 +865,38 @@ impl ProgrammerConnectedClockModeSelected {
         cmd.execute(&mut self.target)
     }
 
-    /// Retrive the operating frequency range of each clock
+    /// Retrive the operating frequency range of each clock. Doesn't change the programmer's state,
+    /// so can be called any number of times.
     pub fn operating_frequencies(&mut self) -> Result<Vec<RangeInclusive<u16>>> {
-        let cmd = command::commands::OperatingFrequencyInquiry {};
+        let cmd = command::commands::OperatingFrequencyInquiry {
+            endianness: self.config.frequency_endianness,
+        };
         cmd.execute(&mut self.target)
     }
 
-    /// Sets a new bit rate for the device connection
+    /// Sets a new bit rate for the device connection. One multiplication ratio must be provided
+    /// for each clock reported by `multiplication_ratios`.
     pub fn set_new_bit_rate(
         mut self,
         bit_rate: u16,
-        input_frequency: u16,
+        input_frequency: command::data::InputFrequency,
         multiplication_ratios: Vec<command::data::MultiplicationRatio>,
     ) -> Result<ProgrammerConnectedNewBitRateSelected> {
+        let clock_count = self.multiplication_ratios()?.len();
+        if multiplication_ratios.len() != clock_count {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                format!(
+                    "device expects {} clock ratios, got {}",
+                    clock_count,
+                    multiplication_ratios.len()
+                ),
+            ));
+        }
+
         let cmd = command::commands::NewBitRateSelection {
             bit_rate: bit_rate,
-            input_frequency: input_frequency,
+            input_frequency: input_frequency.into(),
             multiplication_ratios: multiplication_ratios,
         };
         cmd.execute(&mut self.target)?;
@@ -171,80 +906,571 @@ impl ProgrammerConnectedClockModeSelected {
 
         let cmd = command::commands::NewBitRateSelectionConfirmation {};
         cmd.execute(&mut self.target)?;
+        confirm_status(
+            &mut self.target,
+            &self.config,
+            command::commands::BootProgramStatus::WaitingForTransitionToProgrammingErasureCommandWait,
+        )?;
+
+        debug!(
+            "ClockModeSelected -> NewBitRateSelected ({} bps)",
+            baud_rate
+        );
 
         Ok(ProgrammerConnectedNewBitRateSelected {
             target: self.target,
+            config: self.config,
+            baud_rate,
+            block_size: None,
+            erasure_blocks: None,
+            user_boot_area: None,
+            user_area: None,
         })
     }
+
+    /// Sets a new bit rate for the device connection, choosing the first multiplication ratio for
+    /// each clock which keeps it within its operating frequency range. `desired_bps` must be a
+    /// multiple of 100.
+    pub fn set_bit_rate_auto(
+        mut self,
+        desired_bps: u32,
+        input_frequency: command::data::InputFrequency,
+    ) -> Result<ProgrammerConnectedNewBitRateSelected> {
+        if desired_bps % 100 != 0 || desired_bps / 100 > u16::MAX as u32 {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                format!("bit rate of {} bps cannot be selected", desired_bps),
+            ));
+        }
+
+        let ratios = self.multiplication_ratios()?;
+        let frequencies = self.operating_frequencies()?;
+        let multiplication_ratios =
+            choose_multiplication_ratios(input_frequency, &ratios, &frequencies)?;
+
+        self.set_new_bit_rate(
+            (desired_bps / 100) as u16,
+            input_frequency,
+            multiplication_ratios,
+        )
+    }
+}
+
+fn choose_multiplication_ratios(
+    input_frequency: command::data::InputFrequency,
+    ratios: &[Vec<command::data::MultiplicationRatio>],
+    frequencies: &[RangeInclusive<u16>],
+) -> Result<Vec<command::data::MultiplicationRatio>> {
+    let input_frequency = u16::from(input_frequency);
+
+    ratios
+        .iter()
+        .zip(frequencies)
+        .enumerate()
+        .map(|(clock, (ratios, frequency))| {
+            let in_range = |ratio: &&command::data::MultiplicationRatio| {
+                let clock_frequency = match **ratio {
+                    command::data::MultiplicationRatio::DivideBy(ratio) => {
+                        input_frequency as u32 / ratio as u32
+                    }
+                    command::data::MultiplicationRatio::MultiplyBy(ratio) => {
+                        input_frequency as u32 * ratio as u32
+                    }
+                };
+
+                clock_frequency >= *frequency.start() as u32
+                    && clock_frequency <= *frequency.end() as u32
+            };
+
+            ratios.iter().find(in_range).copied().ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Argument,
+                    format!(
+                        "no multiplication ratio for clock {} gives a frequency between {} and {}",
+                        clock,
+                        frequency.start(),
+                        frequency.end()
+                    ),
+                )
+            })
+        })
+        .collect()
 }
 
 /// A programmer connected to a device, after a new bit rate has been selected
+///
+/// The device no longer accepts the programming unit, erasure block and area inquiries once it
+/// has transitioned to the programming/erasure state, so the results of any made in this state
+/// are carried into the programming/erasure state for the methods which need them.
 pub struct ProgrammerConnectedNewBitRateSelected {
     target: Box<dyn Target>,
+    config: ConnectConfig,
+    baud_rate: u32,
+    block_size: Option<u16>,
+    erasure_blocks: Option<Vec<RangeInclusive<u32>>>,
+    user_boot_area: Option<Vec<RangeInclusive<u32>>>,
+    user_area: Option<Vec<RangeInclusive<u32>>>,
 }
 
 impl ProgrammerConnectedNewBitRateSelected {
+    /// Creates a programmer in this state without communicating with the device, for testing code
+    /// which operates on this state against a mock target. The device must already be in the
+    /// corresponding state for subsequent commands to succeed.
+    #[cfg(feature = "test-util")]
+    pub fn from_target(
+        target: Box<dyn Target>,
+        config: ConnectConfig,
+        baud_rate: u32,
+    ) -> ProgrammerConnectedNewBitRateSelected {
+        ProgrammerConnectedNewBitRateSelected {
+            target,
+            config,
+            baud_rate,
+            block_size: None,
+            erasure_blocks: None,
+            user_boot_area: None,
+            user_area: None,
+        }
+    }
+
+    /// Executes a command, returning the raw bytes of the device's response alongside the parsed
+    /// response, for debugging unexpected inquiry results
+    pub fn execute_raw<C: Command>(&mut self, cmd: &C) -> Result<(C::Response, Vec<u8>)> {
+        cmd.execute_raw(&mut self.target)
+    }
+
+    /// The number of bytes per second which can be transferred at the selected bit rate, e.g. to
+    /// estimate how long programming an image will take
+    pub fn byte_rate(&self) -> u32 {
+        byte_rate(self.baud_rate)
+    }
+
+    /// Retrieves the size of the device's programming unit in bytes
+    pub fn programming_size(&mut self) -> Result<u16> {
+        let cmd = command::commands::ProgrammingSizeInquiry {};
+        let block_size = cmd.execute(&mut self.target)?;
+        self.block_size = Some(block_size);
+
+        Ok(block_size)
+    }
+
     /// Retrieves the regions which comprise the user boot area
     pub fn user_boot_area(&mut self) -> Result<Vec<RangeInclusive<u32>>> {
         let cmd = command::commands::UserBootAreaInformationInquiry {};
-        cmd.execute(&mut self.target)
+        let user_boot_area = cmd.execute(&mut self.target)?;
+        self.user_boot_area = Some(user_boot_area.clone());
+
+        Ok(user_boot_area)
     }
 
     /// Retrieves the regions which comprise the user area
     pub fn user_area(&mut self) -> Result<Vec<RangeInclusive<u32>>> {
         let cmd = command::commands::UserAreaInformationInquiry {};
-        cmd.execute(&mut self.target)
+        let user_area = cmd.execute(&mut self.target)?;
+        self.user_area = Some(user_area.clone());
+
+        Ok(user_area)
     }
 
     /// Retrieves the blocks which can be erased
     pub fn erasure_block(&mut self) -> Result<Vec<RangeInclusive<u32>>> {
         let cmd = command::commands::ErasureBlockInformationInquiry {};
-        cmd.execute(&mut self.target)
+        let erasure_blocks = cmd.execute(&mut self.target)?;
+        self.erasure_blocks = Some(erasure_blocks.clone());
+
+        Ok(erasure_blocks)
     }
 
-    /// Transitions into the programming/erasure wait state
-    pub fn programming_erasure_state_transition(
-        mut self,
-    ) -> Result<ProgrammerConnectedProgrammingErasureState> {
+    /// Retrieves the regions which comprise the user boot area, labelled for display
+    pub fn user_boot_area_map(&mut self) -> Result<AreaMap> {
+        Ok(AreaMap {
+            label: "User boot area",
+            regions: self.user_boot_area()?,
+        })
+    }
+
+    /// Retrieves the regions which comprise the user area, labelled for display
+    pub fn user_area_map(&mut self) -> Result<AreaMap> {
+        Ok(AreaMap {
+            label: "User area",
+            regions: self.user_area()?,
+        })
+    }
+
+    /// Retrieves the blocks which can be erased, labelled for display
+    pub fn erasure_block_map(&mut self) -> Result<AreaMap> {
+        Ok(AreaMap {
+            label: "Erasure block",
+            regions: self.erasure_block()?,
+        })
+    }
+
+    /// Retrieves the regions which comprise the data area, labelled for display, or `None` if
+    /// the device has no data area
+    pub fn data_area_map(&mut self) -> Result<Option<AreaMap>> {
+        let cmd = command::commands::DataAreaInquiry {};
+        if cmd.execute(&mut self.target)? == command::data::DataAreaAvailability::Unavailable {
+            return Ok(None);
+        }
+
+        let cmd = command::commands::DataAreaInformationInquiry {};
+        Ok(Some(AreaMap {
+            label: "Data area",
+            regions: cmd.execute(&mut self.target)?,
+        }))
+    }
+
+    /// Transitions into the programming/erasure wait state. If the device has ID code protection
+    /// enabled, it instead waits for an ID code to be submitted with `submit_id_code`.
+    ///
+    /// Methods of the programming/erasure state which need the erasure blocks or areas use the
+    /// results of `erasure_block`, `user_boot_area` and `user_area` calls made before the
+    /// transition, and fail if the inquiry wasn't made. The programming unit size is needed to
+    /// check block alignment, so it's inquired here if `programming_size` wasn't called.
+    pub fn programming_erasure_state_transition(mut self) -> Result<ProgrammingErasureStateEntry> {
+        if self.block_size.is_none() {
+            self.programming_size()?;
+        }
+
         let cmd = command::commands::ProgrammingErasureStateTransition {};
         let response = cmd.execute(&mut self.target)?;
 
         match response {
             command::commands::IDCodeProtectionStatus::Disabled => {
-                Ok(ProgrammerConnectedProgrammingErasureState {
-                    target: self.target,
-                })
+                debug!("NewBitRateSelected -> ProgrammingErasureState");
+
+                Ok(ProgrammingErasureStateEntry::Entered(
+                    ProgrammerConnectedProgrammingErasureState {
+                        target: self.target,
+                        config: self.config,
+                        baud_rate: self.baud_rate,
+                        block_size: self.block_size,
+                        erasure_blocks: self.erasure_blocks,
+                        user_boot_area: self.user_boot_area,
+                        user_area: self.user_area,
+                    },
+                ))
             }
             command::commands::IDCodeProtectionStatus::Enabled => {
-                panic!("Support for ID codes not implemented")
+                debug!("NewBitRateSelected -> WaitingForIDCode");
+
+                Ok(ProgrammingErasureStateEntry::IDCodeRequired(
+                    ProgrammerConnectedWaitingForIDCode {
+                        target: self.target,
+                        config: self.config,
+                        baud_rate: self.baud_rate,
+                        block_size: self.block_size,
+                        erasure_blocks: self.erasure_blocks,
+                        user_boot_area: self.user_boot_area,
+                        user_area: self.user_area,
+                    },
+                ))
             }
         }
     }
 }
 
+/// Result of transitioning into the programming/erasure state, which depends on whether the
+/// device has ID code protection enabled
+pub enum ProgrammingErasureStateEntry {
+    /// ID code protection is disabled, and the programmer is in the programming/erasure state
+    Entered(ProgrammerConnectedProgrammingErasureState),
+    /// ID code protection is enabled, and the device is waiting for its ID code
+    IDCodeRequired(ProgrammerConnectedWaitingForIDCode),
+}
+
+/// A programmer connected to a device with ID code protection enabled, waiting for the ID code to
+/// be submitted
+pub struct ProgrammerConnectedWaitingForIDCode {
+    target: Box<dyn Target>,
+    config: ConnectConfig,
+    baud_rate: u32,
+    block_size: Option<u16>,
+    erasure_blocks: Option<Vec<RangeInclusive<u32>>>,
+    user_boot_area: Option<Vec<RangeInclusive<u32>>>,
+    user_area: Option<Vec<RangeInclusive<u32>>>,
+}
+
+impl ProgrammerConnectedWaitingForIDCode {
+    /// Creates a programmer in this state without communicating with the device, for testing code
+    /// which operates on this state against a mock target. The device must already be in the
+    /// corresponding state for subsequent commands to succeed.
+    ///
+    /// `block_size`, `erasure_blocks`, `user_boot_area` and `user_area` take the place of the
+    /// results of the inquiries made before transitioning to the programming/erasure state, with
+    /// `None` for an inquiry which wasn't made.
+    #[cfg(feature = "test-util")]
+    pub fn from_target(
+        target: Box<dyn Target>,
+        config: ConnectConfig,
+        baud_rate: u32,
+        block_size: Option<u16>,
+        erasure_blocks: Option<Vec<RangeInclusive<u32>>>,
+        user_boot_area: Option<Vec<RangeInclusive<u32>>>,
+        user_area: Option<Vec<RangeInclusive<u32>>>,
+    ) -> ProgrammerConnectedWaitingForIDCode {
+        ProgrammerConnectedWaitingForIDCode {
+            target,
+            config,
+            baud_rate,
+            block_size,
+            erasure_blocks,
+            user_boot_area,
+            user_area,
+        }
+    }
+
+    /// Submits the device's ID code, transitioning into the programming/erasure wait state if it
+    /// matches. Depending on the device, repeatedly submitting an incorrect ID code may cause it
+    /// to erase its user area and user boot area.
+    pub fn submit_id_code(
+        mut self,
+        id_code: &[u8; 16],
+    ) -> Result<ProgrammerConnectedProgrammingErasureState> {
+        let cmd = command::commands::IDCodeCheck { id_code: *id_code };
+        cmd.execute(&mut self.target)?;
+
+        debug!("WaitingForIDCode -> ProgrammingErasureState");
+
+        Ok(ProgrammerConnectedProgrammingErasureState {
+            target: self.target,
+            config: self.config,
+            baud_rate: self.baud_rate,
+            block_size: self.block_size,
+            erasure_blocks: self.erasure_blocks,
+            user_boot_area: self.user_boot_area,
+            user_area: self.user_area,
+        })
+    }
+}
+
 /// A programmer connected to a device, waiting for programming selection commands
 pub struct ProgrammerConnectedProgrammingErasureState {
     target: Box<dyn Target>,
+    config: ConnectConfig,
+    baud_rate: u32,
+    block_size: Option<u16>,
+    erasure_blocks: Option<Vec<RangeInclusive<u32>>>,
+    user_boot_area: Option<Vec<RangeInclusive<u32>>>,
+    user_area: Option<Vec<RangeInclusive<u32>>>,
 }
 
 impl ProgrammerConnectedProgrammingErasureState {
+    /// Creates a programmer in this state without communicating with the device, for testing code
+    /// which operates on this state against a mock target. The device must already be in the
+    /// corresponding state for subsequent commands to succeed.
+    ///
+    /// `block_size`, `erasure_blocks`, `user_boot_area` and `user_area` take the place of the
+    /// results of the inquiries made before transitioning to the programming/erasure state, with
+    /// `None` for an inquiry which wasn't made.
+    #[cfg(feature = "test-util")]
+    pub fn from_target(
+        target: Box<dyn Target>,
+        config: ConnectConfig,
+        baud_rate: u32,
+        block_size: Option<u16>,
+        erasure_blocks: Option<Vec<RangeInclusive<u32>>>,
+        user_boot_area: Option<Vec<RangeInclusive<u32>>>,
+        user_area: Option<Vec<RangeInclusive<u32>>>,
+    ) -> ProgrammerConnectedProgrammingErasureState {
+        ProgrammerConnectedProgrammingErasureState {
+            target,
+            config,
+            baud_rate,
+            block_size,
+            erasure_blocks,
+            user_boot_area,
+            user_area,
+        }
+    }
+
+    /// The number of bytes per second which can be transferred at the selected bit rate
+    pub fn byte_rate(&self) -> u32 {
+        byte_rate(self.baud_rate)
+    }
+
     /// Selects the user area and data area for programming
     pub fn program_user_or_data_area(mut self) -> Result<ProgrammerConnectedWaitingForData> {
         let cmd = command::commands::UserDataAreaProgrammingSelection {};
         cmd.execute(&mut self.target)?;
 
+        debug!("ProgrammingErasureState -> WaitingForData (user/data area)");
+
         Ok(ProgrammerConnectedWaitingForData {
             target: self.target,
+            config: self.config,
+            baud_rate: self.baud_rate,
+            block_size: self.block_size,
+            erasure_blocks: self.erasure_blocks,
+            user_boot_area: self.user_boot_area,
+            user_area: self.user_area,
         })
     }
 
-    /// Read `size` bytes of memory starting from `start_address`
-    pub fn read_memory(
+    /// Erases a single erasure block, then programs `data` starting from the beginning of the
+    /// block. Erasing and programming one block at a time keeps each operation short on devices
+    /// where erasing everything up front would take too long. `erased_value` is the value the
+    /// device's flash reads as once erased, as given by [`Image::erased_value`]; chunks made up
+    /// entirely of it are skipped, and the final chunk is padded with it.
+    pub fn erase_and_program_block(
         &mut self,
-        area: command::data::MemoryArea,
-        start_address: u32,
+        block_number: u8,
+        data: &[u8],
+        erased_value: u8,
+    ) -> Result<()> {
+        let block_size = programming_block_size(self.block_size)?;
+        let erasure_blocks = inquired(&self.erasure_blocks, "erasure blocks", "erasure_block")?;
+        let block = match erasure_blocks.get(block_number as usize) {
+            Some(block) => block.clone(),
+            None => {
+                return Err(Error::new(
+                    ErrorKind::Argument,
+                    format!("device has no erasure block {}", block_number),
+                ))
+            }
+        };
+
+        let block_length = (block.end() - block.start()) as usize + 1;
+        if data.len() > block_length {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                format!(
+                    "{} bytes of data does not fit in erasure block {} ({} bytes)",
+                    data.len(),
+                    block_number,
+                    block_length
+                ),
+            ));
+        }
+
+        let cmd = command::commands::ErasureSelection {};
+        cmd.execute(&mut self.target)?;
+
+        let cmd = command::commands::BlockErasure {
+            block: block_number,
+        };
+        execute_long_operation(&mut self.target, &cmd, self.config.timeouts)?;
+
+        let cmd = command::commands::BlockErasure { block: 0xFF };
+        cmd.execute(&mut self.target)?;
+
+        let cmd = command::commands::UserDataAreaProgrammingSelection {};
+        cmd.execute(&mut self.target)?;
+
+        let mut result = Ok(());
+        for (i, chunk) in data.chunks(block_size).enumerate() {
+            // The block has just been erased, so there's no need to program blank chunks
+            if chunk.iter().all(|&x| x == erased_value) {
+                continue;
+            }
+
+            let address = block.start() + (i * block_size) as u32;
+            let mut data = [erased_value; 256];
+            data[..chunk.len()].copy_from_slice(chunk);
+
+            let cmd = command::commands::X256ByteProgramming { address, data };
+            result = cmd
+                .execute(&mut self.target)
+                .map_err(|error| block_error(error, address, chunk.len()));
+            if result.is_err() {
+                break;
+            }
+        }
+
+        end_programming(&mut self.target, result)
+    }
+
+    /// Erases every erasure block overlapping `range`. The range must start at the beginning of
+    /// an erasure block and end at the end of one, so no memory outside it is erased.
+    pub fn erase_range(&mut self, range: RangeInclusive<u32>) -> Result<()> {
+        let blocks = inquired(&self.erasure_blocks, "erasure blocks", "erasure_block")?
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| block.start() <= range.end() && range.start() <= block.end())
+            .collect::<Vec<_>>();
+
+        let starts_on_boundary = blocks
+            .iter()
+            .any(|(_, block)| block.start() == range.start());
+        let ends_on_boundary = blocks.iter().any(|(_, block)| block.end() == range.end());
+        if !starts_on_boundary || !ends_on_boundary {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                format!(
+                    "{:#X}-{:#X} is not aligned to erasure block boundaries",
+                    range.start(),
+                    range.end()
+                ),
+            ));
+        }
+
+        let block_numbers = blocks
+            .iter()
+            .map(|&(i, _)| erasure_block_number(i))
+            .collect::<Result<Vec<_>>>()?;
+
+        self.erase_blocks(&block_numbers)
+    }
+
+    /// Erases a single erasure block, numbered as in `erasure_block`'s result
+    pub fn erase_block(&mut self, block_number: u8) -> Result<()> {
+        let erasure_blocks = inquired(&self.erasure_blocks, "erasure blocks", "erasure_block")?;
+        if block_number as usize >= erasure_blocks.len() {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                format!("device has no erasure block {}", block_number),
+            ));
+        }
+        let block_number = erasure_block_number(block_number as usize)?;
+
+        self.erase_blocks(&[block_number])
+    }
+
+    /// Erases every erasure block, one at a time
+    pub fn erase_all_blocks(&mut self) -> Result<()> {
+        let erasure_blocks = inquired(&self.erasure_blocks, "erasure blocks", "erasure_block")?;
+        let block_numbers = (0..erasure_blocks.len())
+            .map(erasure_block_number)
+            .collect::<Result<Vec<_>>>()?;
+
+        self.erase_blocks(&block_numbers)
+    }
+
+    fn erase_blocks(&mut self, block_numbers: &[u8]) -> Result<()> {
+        let cmd = command::commands::ErasureSelection {};
+        cmd.execute(&mut self.target)?;
+
+        for &block in block_numbers {
+            let cmd = command::commands::BlockErasure { block };
+            execute_long_operation(&mut self.target, &cmd, self.config.timeouts)?;
+        }
+
+        let cmd = command::commands::BlockErasure { block: 0xFF };
+        cmd.execute(&mut self.target)
+    }
+
+    /// Read `size` bytes of memory starting from `start_address`
+    ///
+    /// The Boot Mode protocol has no command to read a device's unique ID. On devices which
+    /// store an ID in the user boot or user area, it can be read with this method instead.
+    pub fn read_memory(
+        &mut self,
+        area: command::data::MemoryArea,
+        start_address: u32,
         size: u32,
     ) -> Result<Vec<u8>> {
+        let (regions, area_name) = match area {
+            command::data::MemoryArea::UserBootArea => (&self.user_boot_area, "user boot area"),
+            command::data::MemoryArea::UserArea => (&self.user_area, "user area"),
+        };
+
+        // Checked here rather than left to the device, which only reports an address or data
+        // size error without saying which addresses are valid. If the area wasn't inquired about
+        // before the transition, only the device checks the range.
+        if let Some(regions) = regions {
+            check_read_bounds(regions, area_name, start_address, size)?;
+        }
+
         let cmd = command::commands::MemoryRead {
             area,
             start_address,
@@ -253,27 +1479,612 @@ impl ProgrammerConnectedProgrammingErasureState {
         cmd.execute(&mut self.target)
     }
 
+    /// Reads `size` bytes from the user boot area, starting at `start_address`
+    pub fn read_user_boot_memory(&mut self, start_address: u32, size: u32) -> Result<Vec<u8>> {
+        self.read_memory(command::data::MemoryArea::UserBootArea, start_address, size)
+    }
+
+    /// Reads `size` bytes from the user area, starting at `start_address`
+    pub fn read_user_memory(&mut self, start_address: u32, size: u32) -> Result<Vec<u8>> {
+        self.read_memory(command::data::MemoryArea::UserArea, start_address, size)
+    }
+
+    /// Reads the entire user area into an image, e.g. to save the device's firmware to a file or
+    /// to compare it against an image offline
+    pub fn read_user_area_image(&mut self) -> Result<Image> {
+        let user_area = inquired(&self.user_area, "user area", "user_area")?.clone();
+        let mut image = Image::new(&user_area);
+
+        for region in user_area {
+            self.read_range_chunks(
+                command::data::MemoryArea::UserArea,
+                region,
+                |address, data| image.add_data(address, data),
+            )?;
+        }
+
+        Ok(image)
+    }
+
+    /// Reads `range` and calculates the standard CRC-32 (as used by zlib and most toolchains) of
+    /// its contents, for comparison against a CRC recorded when the image was built
+    pub fn read_crc32(
+        &mut self,
+        area: command::data::MemoryArea,
+        range: RangeInclusive<u32>,
+    ) -> Result<u32> {
+        let mut crc = !0u32;
+        self.read_range_chunks(area, range, |_, data| crc = crc32_update(crc, data))?;
+
+        Ok(!crc)
+    }
+
+    // Reads `range` in chunks of up to 256 bytes, passing the address and data of each chunk to
+    // `f` in turn
+    fn read_range_chunks(
+        &mut self,
+        area: command::data::MemoryArea,
+        range: RangeInclusive<u32>,
+        mut f: impl FnMut(u32, &[u8]),
+    ) -> Result<()> {
+        let mut address = *range.start();
+        loop {
+            let size = (range.end() - address).min(255) + 1;
+            let data = self.read_sized(area, address, size)?;
+            f(address, &data);
+
+            // Checked this way round so a range ending at 0xFFFFFFFF doesn't overflow
+            if range.end() - address < size {
+                return Ok(());
+            }
+            address += size;
+        }
+    }
+
+    fn read_sized(
+        &mut self,
+        area: command::data::MemoryArea,
+        address: u32,
+        size: u32,
+    ) -> Result<Vec<u8>> {
+        let data = self.read_memory(area, address, size)?;
+        if data.len() != size as usize {
+            return Err(Error::new(
+                ErrorKind::Protocol,
+                format!("expected {} bytes, target returned {}", size, data.len()),
+            ));
+        }
+
+        Ok(data)
+    }
+
+    /// Reads `expected.len()` bytes starting from `address`, returning true if they match
+    /// `expected`
+    pub fn verify_range(
+        &mut self,
+        area: command::data::MemoryArea,
+        address: u32,
+        expected: &[u8],
+    ) -> Result<bool> {
+        let data = self.read_sized(area, address, expected.len() as u32)?;
+        Ok(data == expected)
+    }
+
+    /// Reads the byte at `address`
+    pub fn read_u8(&mut self, area: command::data::MemoryArea, address: u32) -> Result<u8> {
+        let data = self.read_sized(area, address, 1)?;
+        Ok(data[0])
+    }
+
+    /// Reads the 16-bit value starting at `address`, stored in the given byte order
+    pub fn read_u16(
+        &mut self,
+        area: command::data::MemoryArea,
+        address: u32,
+        endianness: command::data::Endianness,
+    ) -> Result<u16> {
+        let data = self.read_sized(area, address, 2)?;
+        Ok(endianness.u16_from_bytes([data[0], data[1]]))
+    }
+
+    /// Reads the 32-bit value starting at `address`, stored in the given byte order
+    pub fn read_u32(
+        &mut self,
+        area: command::data::MemoryArea,
+        address: u32,
+        endianness: command::data::Endianness,
+    ) -> Result<u32> {
+        let data = self.read_sized(area, address, 4)?;
+        Ok(endianness.u32_from_bytes([data[0], data[1], data[2], data[3]]))
+    }
+
     /// Requests the checksum of the user boot area
     pub fn user_boot_area_checksum(&mut self) -> Result<u32> {
         let cmd = command::commands::UserBootAreaChecksum {};
-        cmd.execute(&mut self.target)
+        execute_long_operation(&mut self.target, &cmd, self.config.timeouts)
     }
 
     /// Requests the checksum of the user area
     pub fn user_area_checksum(&mut self) -> Result<u32> {
         let cmd = command::commands::UserAreaChecksum {};
-        cmd.execute(&mut self.target)
+        execute_long_operation(&mut self.target, &cmd, self.config.timeouts)
+    }
+
+    /// Checks whether the user boot area is blank
+    pub fn user_boot_area_blank(&mut self) -> Result<bool> {
+        let cmd = command::commands::UserBootAreaBlankCheck {};
+        let erasure_state = execute_long_operation(&mut self.target, &cmd, self.config.timeouts)?;
+
+        Ok(erasure_state == command::data::ErasureState::Blank)
+    }
+
+    /// Checks whether the user area is blank
+    pub fn user_area_blank(&mut self) -> Result<bool> {
+        let cmd = command::commands::UserAreaBlankCheck {};
+        let erasure_state = execute_long_operation(&mut self.target, &cmd, self.config.timeouts)?;
+
+        Ok(erasure_state == command::data::ErasureState::Blank)
+    }
+
+    /// Checks whether both the user boot area and the user area are blank
+    pub fn is_device_blank(&mut self) -> Result<bool> {
+        let user_boot_area_blank = self.user_boot_area_blank()?;
+        let user_area_blank = self.user_area_blank()?;
+
+        Ok(user_boot_area_blank && user_area_blank)
+    }
+
+    /// Blank checks the user boot area and the user area, then erases the erasure blocks of only
+    /// the areas which aren't blank. Returns true if anything was erased, and an error if an area
+    /// isn't blank but no erasure block covers it.
+    pub fn ensure_erased(&mut self) -> Result<bool> {
+        let user_boot_area =
+            inquired(&self.user_boot_area, "user boot area", "user_boot_area")?.clone();
+        let user_area = inquired(&self.user_area, "user area", "user_area")?.clone();
+        let erasure_blocks =
+            inquired(&self.erasure_blocks, "erasure blocks", "erasure_block")?.clone();
+
+        let user_boot_area_blank = self.user_boot_area_blank()?;
+        let user_area_blank = self.user_area_blank()?;
+
+        let mut block_indices = vec![];
+        for (blank, areas, name) in [
+            (user_boot_area_blank, user_boot_area, "user boot area"),
+            (user_area_blank, user_area, "user area"),
+        ]
+        .iter()
+        {
+            if *blank {
+                continue;
+            }
+
+            let overlapping = erasure_blocks
+                .iter()
+                .enumerate()
+                .filter(|(_, block)| {
+                    areas
+                        .iter()
+                        .any(|area| block.start() <= area.end() && area.start() <= block.end())
+                })
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+
+            if overlapping.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::Protocol,
+                    format!("{} is not blank but no erasure block covers it", name),
+                ));
+            }
+
+            block_indices.extend(overlapping);
+        }
+
+        if block_indices.is_empty() {
+            return Ok(false);
+        }
+
+        block_indices.sort_unstable();
+        block_indices.dedup();
+        let block_numbers = block_indices
+            .into_iter()
+            .map(erasure_block_number)
+            .collect::<Result<Vec<_>>>()?;
+
+        self.erase_blocks(&block_numbers)?;
+
+        Ok(true)
+    }
+
+    /// Programs an image covering both the user boot area and the user area in 256 byte blocks,
+    /// selecting each area for programming in turn. Data outside of both areas is discarded, and
+    /// an area is skipped entirely if the image has no data for it.
+    pub fn program_full_image(&mut self, image: &Image) -> Result<()> {
+        let block_size = programming_block_size(self.block_size)?;
+        let user_boot_area = inquired(&self.user_boot_area, "user boot area", "user_boot_area")?;
+        let user_area = inquired(&self.user_area, "user area", "user_area")?;
+        let (user_boot_image, user_image) = image.split_by_areas(user_boot_area, user_area);
+
+        if user_boot_image.programmable_block_count(block_size) != 0 {
+            let cmd = command::commands::UserBootAreaProgrammingSelection {};
+            cmd.execute(&mut self.target)?;
+            self.program_selected_area(&user_boot_image)?;
+        }
+
+        if user_image.programmable_block_count(block_size) != 0 {
+            let cmd = command::commands::UserDataAreaProgrammingSelection {};
+            cmd.execute(&mut self.target)?;
+            self.program_selected_area(&user_image)?;
+        }
+
+        Ok(())
+    }
+
+    // Programs an image into the area already selected for programming, then ends programming,
+    // even if a block fails to program
+    fn program_selected_area(&mut self, image: &Image) -> Result<()> {
+        let block_size = programming_block_size(self.block_size)?;
+        let mut result = Ok(());
+        for block in image.programmable_blocks(block_size) {
+            let mut data = [0u8; 256];
+            data.copy_from_slice(block.data);
+
+            let cmd = command::commands::X256ByteProgramming {
+                address: block.start_address,
+                data,
+            };
+            result = cmd
+                .execute(&mut self.target)
+                .map_err(|error| block_error(error, block.start_address, block.data.len()));
+            if result.is_err() {
+                break;
+            }
+        }
+
+        end_programming(&mut self.target, result)
+    }
+
+    /// Programs an image into the user area in 256 byte blocks, then verifies it as specified
+    /// by `verify_mode`. Stops at the first block which fails to program or read back.
+    pub fn program_image(&mut self, image: &Image, verify_mode: VerifyMode) -> Result<FlashReport> {
+        self.program_image_inner(image, verify_mode, false)
+    }
+
+    /// Like `program_image`, but continues past blocks which fail to program or read back,
+    /// recording each failure in the report's `block_results`
+    pub fn program_image_continue_on_error(
+        &mut self,
+        image: &Image,
+        verify_mode: VerifyMode,
+    ) -> Result<FlashReport> {
+        self.program_image_inner(image, verify_mode, true)
+    }
+
+    fn program_image_inner(
+        &mut self,
+        image: &Image,
+        verify_mode: VerifyMode,
+        continue_on_error: bool,
+    ) -> Result<FlashReport> {
+        let block_size = programming_block_size(self.block_size)?;
+        let cmd = command::commands::UserDataAreaProgrammingSelection {};
+        cmd.execute(&mut self.target)?;
+
+        let mut block_results = vec![];
+        let mut result = Ok(());
+        for block in image.programmable_blocks(block_size) {
+            let mut data = [0u8; 256];
+            data.copy_from_slice(block.data);
+
+            let cmd = command::commands::X256ByteProgramming {
+                address: block.start_address,
+                data,
+            };
+            let block_result = cmd
+                .execute(&mut self.target)
+                .map_err(|error| block_error(error, block.start_address, block.data.len()));
+
+            let programming_error = match block_result {
+                Ok(()) => None,
+                Err(error) if continue_on_error => Some(error),
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            };
+
+            block_results.push(BlockResult {
+                address: block.start_address,
+                length: block.data.len(),
+                programming_error,
+                matched: None,
+            });
+        }
+
+        end_programming(&mut self.target, result)?;
+
+        let mut report = self.verify_image_inner(image, verify_mode, continue_on_error)?;
+
+        // Merge the programming results with any read back results
+        for result in &mut block_results {
+            let verify_result = report
+                .block_results
+                .iter()
+                .find(|verify_result| verify_result.address == result.address);
+
+            if let Some(verify_result) = verify_result {
+                result.matched = verify_result.matched;
+                if result.programming_error.is_none() {
+                    result.programming_error = verify_result.programming_error.clone();
+                }
+            }
+        }
+
+        if block_results
+            .iter()
+            .any(|result| result.programming_error.is_some())
+        {
+            report.verified = false;
+        }
+        report.block_results = block_results;
+
+        Ok(report)
+    }
+
+    /// Programs an image into the user area in 256 byte blocks, reading each block back and
+    /// comparing it with the image as soon as it has been programmed. Stops at the first block
+    /// which fails to program or doesn't match, which is recorded in the report.
+    ///
+    /// The boot program doesn't accept reads while waiting for programming data, so programming
+    /// is reselected for every block. This is slower than `program_image`, but pinpoints the
+    /// failing block without programming the rest of the image.
+    pub fn program_and_verify_image(&mut self, image: &Image) -> Result<FlashReport> {
+        let block_size = programming_block_size(self.block_size)?;
+        let mut report = FlashReport {
+            verify_mode: VerifyMode::FullReadback,
+            verified: false,
+            mismatched_blocks: Vec::new(),
+            block_results: Vec::new(),
+            checksums: None,
+        };
+
+        for block in image.programmable_blocks(block_size) {
+            let mut data = [0u8; 256];
+            data.copy_from_slice(block.data);
+
+            let cmd = command::commands::UserDataAreaProgrammingSelection {};
+            cmd.execute(&mut self.target)?;
+
+            let cmd = command::commands::X256ByteProgramming {
+                address: block.start_address,
+                data,
+            };
+            let result = cmd
+                .execute(&mut self.target)
+                .map_err(|error| block_error(error, block.start_address, block.data.len()));
+            end_programming(&mut self.target, Ok(()))?;
+
+            if let Err(error) = result {
+                report.block_results.push(BlockResult {
+                    address: block.start_address,
+                    length: block.data.len(),
+                    programming_error: Some(error),
+                    matched: None,
+                });
+                return Ok(report);
+            }
+
+            let programmed_data =
+                self.read_user_memory(block.start_address, block.data.len() as u32)?;
+            let matched = block.matches(&programmed_data);
+
+            report.block_results.push(BlockResult {
+                address: block.start_address,
+                length: block.data.len(),
+                programming_error: None,
+                matched: Some(matched),
+            });
+
+            if !matched {
+                report.mismatched_blocks.push(block.start_address);
+                return Ok(report);
+            }
+        }
+
+        report.verified = true;
+
+        Ok(report)
+    }
+
+    /// Checks the contents of the user area against an image, without programming it. Stops at
+    /// the first block which fails to read back.
+    pub fn verify_image(&mut self, image: &Image, verify_mode: VerifyMode) -> Result<FlashReport> {
+        self.verify_image_inner(image, verify_mode, false)
+    }
+
+    /// Like `verify_image`, but continues past blocks which fail to read back, recording each
+    /// failure in the report's `block_results`
+    pub fn verify_image_continue_on_error(
+        &mut self,
+        image: &Image,
+        verify_mode: VerifyMode,
+    ) -> Result<FlashReport> {
+        self.verify_image_inner(image, verify_mode, true)
+    }
+
+    fn verify_image_inner(
+        &mut self,
+        image: &Image,
+        verify_mode: VerifyMode,
+        continue_on_error: bool,
+    ) -> Result<FlashReport> {
+        let mut report = FlashReport {
+            verify_mode,
+            verified: false,
+            mismatched_blocks: Vec::new(),
+            block_results: Vec::new(),
+            checksums: None,
+        };
+
+        match verify_mode {
+            VerifyMode::None => {}
+            VerifyMode::Checksum => {
+                let checksums = ChecksumComparison {
+                    image: image.checksum(),
+                    device: self.user_area_checksum()?,
+                };
+
+                report.verified = checksums.image == checksums.device;
+                report.checksums = Some(checksums);
+            }
+            VerifyMode::FullReadback => {
+                for block in image.programmable_blocks(256) {
+                    let result =
+                        self.read_user_memory(block.start_address, block.data.len() as u32);
+
+                    let mut block_result = BlockResult {
+                        address: block.start_address,
+                        length: block.data.len(),
+                        programming_error: None,
+                        matched: None,
+                    };
+
+                    match result {
+                        Ok(programmed_data) => {
+                            let matched = block.matches(&programmed_data);
+                            if !matched {
+                                report.mismatched_blocks.push(block.start_address);
+                            }
+
+                            block_result.matched = Some(matched);
+                        }
+                        Err(error) if continue_on_error => {
+                            block_result.programming_error = Some(error);
+                        }
+                        Err(error) => return Err(error),
+                    }
+
+                    report.block_results.push(block_result);
+                }
+
+                report.verified = report
+                    .block_results
+                    .iter()
+                    .all(|result| result.matched == Some(true));
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Consumes the programmer, returning the target so it can be used directly
+    pub fn into_target(self) -> Box<dyn Target> {
+        self.target
+    }
+
+    /// Runs `f` with direct access to the target, e.g. to send application-specific bytes to the
+    /// device after programming. The boot program is left in whatever state `f` leaves it in.
+    pub fn with_target<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut dyn Target) -> io::Result<()>,
+    {
+        Ok(f(self.target.as_mut())?)
+    }
+
+    /// Ends the session by resetting the device into single-chip mode, so it starts running the
+    /// programmed firmware. Targets which can't reset the device prompt the user to do so.
+    pub fn run(mut self) -> io::Result<()> {
+        self.target.reset_into(OperatingMode::SingleChip)
+    }
+
+    /// Ends the session, consuming the programmer, optionally requesting the checksums of the
+    /// user boot area and user area
+    pub fn finish(mut self, checksums: bool) -> Result<SessionSummary> {
+        let mut summary = SessionSummary {
+            user_boot_area_checksum: None,
+            user_area_checksum: None,
+        };
+
+        if checksums {
+            summary.user_boot_area_checksum = Some(self.user_boot_area_checksum()?);
+            summary.user_area_checksum = Some(self.user_area_checksum()?);
+        }
+
+        Ok(summary)
     }
 }
 
 /// A programmer connected to a device, waiting for data to be programmed into the selected area
+///
+/// While waiting for programming data, the boot program only accepts further programming
+/// commands, so checksums cannot be requested between blocks. To checkpoint a long programming
+/// run, call `end`, request the checksum from the programming/erasure state, then reselect the
+/// area to continue programming.
 pub struct ProgrammerConnectedWaitingForData {
     target: Box<dyn Target>,
+    config: ConnectConfig,
+    baud_rate: u32,
+    block_size: Option<u16>,
+    erasure_blocks: Option<Vec<RangeInclusive<u32>>>,
+    user_boot_area: Option<Vec<RangeInclusive<u32>>>,
+    user_area: Option<Vec<RangeInclusive<u32>>>,
 }
 
 impl ProgrammerConnectedWaitingForData {
-    /// Writes a block of data to the device
+    /// Creates a programmer in this state without communicating with the device, for testing code
+    /// which operates on this state against a mock target. The device must already be in the
+    /// corresponding state for subsequent commands to succeed.
+    ///
+    /// `block_size`, `erasure_blocks`, `user_boot_area` and `user_area` take the place of the
+    /// results of the inquiries made before transitioning to the programming/erasure state, with
+    /// `None` for an inquiry which wasn't made.
+    #[cfg(feature = "test-util")]
+    pub fn from_target(
+        target: Box<dyn Target>,
+        config: ConnectConfig,
+        baud_rate: u32,
+        block_size: Option<u16>,
+        erasure_blocks: Option<Vec<RangeInclusive<u32>>>,
+        user_boot_area: Option<Vec<RangeInclusive<u32>>>,
+        user_area: Option<Vec<RangeInclusive<u32>>>,
+    ) -> ProgrammerConnectedWaitingForData {
+        ProgrammerConnectedWaitingForData {
+            target,
+            config,
+            baud_rate,
+            block_size,
+            erasure_blocks,
+            user_boot_area,
+            user_area,
+        }
+    }
+
+    /// The number of bytes per second which can be transferred at the selected bit rate
+    pub fn byte_rate(&self) -> u32 {
+        byte_rate(self.baud_rate)
+    }
+
+    /// The size of the device's programming unit in bytes, as inquired before transitioning to
+    /// the programming/erasure state, or `None` if the programmer was created with `from_target`
+    /// without one
+    pub fn block_size(&self) -> Option<u16> {
+        self.block_size
+    }
+
+    /// Writes a block of data to the device. The address must be aligned to the programming unit
+    /// size, which is checked if it's known.
     pub fn program_block(&mut self, address: u32, data: [u8; 256]) -> Result<()> {
+        if let Some(block_size) = self.block_size {
+            if block_size != 0 && address % block_size as u32 != 0 {
+                return Err(Error::new(
+                    ErrorKind::Argument,
+                    format!(
+                        "address {:#X} is not aligned to the {} byte programming unit",
+                        address, block_size
+                    ),
+                ));
+            }
+        }
+
         let cmd = command::commands::X256ByteProgramming {
             address: address,
             data: data,
@@ -281,7 +2092,42 @@ impl ProgrammerConnectedWaitingForData {
         cmd.execute(&mut self.target)
     }
 
-    /// Finishes programming
+    /// Programs blocks as they are produced by `blocks`, so an image never needs to be held in
+    /// memory in its entirety. Each block is a start address and up to 256 bytes of data; shorter
+    /// blocks are padded with `erased_value`, the value the device's flash reads as once erased.
+    pub fn program_blocks_from(
+        &mut self,
+        blocks: impl Iterator<Item = (u32, Vec<u8>)>,
+        erased_value: u8,
+    ) -> Result<()> {
+        for (address, block) in blocks {
+            if block.len() > 256 {
+                return Err(Error::new(
+                    ErrorKind::Argument,
+                    format!(
+                        "block at {:#X} is {} bytes, longer than 256 bytes",
+                        address,
+                        block.len()
+                    ),
+                ));
+            }
+
+            let mut data = [erased_value; 256];
+            data[..block.len()].copy_from_slice(&block);
+            self.program_block(address, data)
+                .map_err(|error| block_error(error, address, block.len()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Consumes the programmer, returning the target so it can be used directly
+    pub fn into_target(self) -> Box<dyn Target> {
+        self.target
+    }
+
+    /// Finishes programming, returning to the programming/erasure state where checksums can be
+    /// requested
     pub fn end(mut self) -> Result<ProgrammerConnectedProgrammingErasureState> {
         let cmd = command::commands::X256ByteProgramming {
             address: 0xFFFFFFFF,
@@ -289,8 +2135,1661 @@ impl ProgrammerConnectedWaitingForData {
         };
         cmd.execute(&mut self.target)?;
 
+        debug!("WaitingForData -> ProgrammingErasureState");
+
         Ok(ProgrammerConnectedProgrammingErasureState {
             target: self.target,
+            config: self.config,
+            baud_rate: self.baud_rate,
+            block_size: self.block_size,
+            erasure_blocks: self.erasure_blocks,
+            user_boot_area: self.user_boot_area,
+            user_area: self.user_area,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use std::sync::Mutex;
+
+    fn assert_send<T: Send>() {}
+
+    #[derive(Default)]
+    struct SilentTarget {
+        baud_rates: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl Target for SilentTarget {
+        fn clear_buffers(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_baud_rate(&mut self, baud_rate: u32) -> io::Result<()> {
+            self.baud_rates.lock().unwrap().push(baud_rate);
+            Ok(())
+        }
+
+        fn bytes_to_read(&mut self) -> io::Result<u32> {
+            Ok(0)
+        }
+
+        fn reset_into(&mut self, _operating_mode: OperatingMode) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl io::Read for SilentTarget {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    impl io::Write for SilentTarget {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn connect_cancelled() {
+        let config = ConnectConfig {
+            cancel: Some(Arc::new(AtomicBool::new(true))),
+            ..ConnectConfig::default()
+        };
+        let prog = Programmer::with_config(Box::new(SilentTarget::default()), config);
+
+        let result = prog.connect();
+
+        assert_eq!(
+            result.err(),
+            Some(Error::new(ErrorKind::Connect, "cancelled"))
+        );
+    }
+
+    #[test]
+    fn connect_tries_only_fixed_baud_rate() {
+        let baud_rates = Arc::new(Mutex::new(vec![]));
+        let target = SilentTarget {
+            baud_rates: baud_rates.clone(),
+        };
+        let config = ConnectConfig {
+            fixed_baud_rate: Some(9600),
+            ..ConnectConfig::default()
+        };
+        let prog = Programmer::with_config(Box::new(target), config);
+
+        let result = prog.connect();
+
+        assert_eq!(
+            result.err(),
+            Some(Error::new(ErrorKind::Connect, "no response from target"))
+        );
+        assert_eq!(*baud_rates.lock().unwrap(), vec![9600]);
+    }
+
+    #[test]
+    fn connect_retries_lost_confirmation() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .write(&[0x55])
+            .read(&[0x12])
+            .write(&[0x55])
+            .read(&[0xE6])
+            .build();
+
+        Programmer::new(Box::new(target.clone())).connect()?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn connect_gives_up_after_confirmation_attempts() {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .read(&[0x12])
+            .write(&[0x55])
+            .read(&[0x12])
+            .write(&[0x55])
+            .read(&[0x12])
+            .build();
+
+        let result = Programmer::new(Box::new(target.clone())).connect();
+
+        assert_eq!(
+            result.err(),
+            Some(Error::new(ErrorKind::Connect, "bad response from target"))
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn connect_adopts_device_waiting_for_device_selection() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x80])
+            .write(&[0x4F])
+            .read(&[0x5F, 0x02, 0x11, 0x00, 0x8E])
+            .build();
+        let config = ConnectConfig {
+            skip_reset: true,
+            ..ConnectConfig::default()
+        };
+
+        Programmer::with_config(Box::new(target.clone()), config).connect()?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn connect_rejects_garbage_status_reply() {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x80])
+            .write(&[0x4F])
+            .read(&[0x12])
+            .build();
+        let config = ConnectConfig {
+            skip_reset: true,
+            ..ConnectConfig::default()
+        };
+
+        let result = Programmer::with_config(Box::new(target.clone()), config).connect();
+
+        assert_eq!(
+            result.err(),
+            Some(Error::new(ErrorKind::Connect, "bad response from target"))
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn connect_rejects_device_already_past_device_selection() {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x80])
+            .write(&[0x4F])
+            .read(&[0x5F, 0x02, 0x3F, 0x00, 0x60])
+            .build();
+        let config = ConnectConfig {
+            skip_reset: true,
+            ..ConnectConfig::default()
+        };
+
+        let result = Programmer::with_config(Box::new(target.clone()), config).connect();
+
+        assert_eq!(
+            result.err().map(|error| error.kind),
+            Some(ErrorKind::Connect)
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn area_map_display() {
+        let user_area = AreaMap {
+            label: "User area",
+            regions: vec![0xFFF00000..=0xFFFFFFFF],
+        };
+        let erasure_blocks = AreaMap {
+            label: "Erasure block",
+            regions: vec![0x00100000..=0x00101FFF, 0x00102000..=0x0010207F],
+        };
+
+        assert_eq!(user_area.size(), 0x100000);
+        assert_eq!(
+            user_area.to_string(),
+            "User area: 0xFFF00000-0xFFFFFFFF (1 MiB)\n"
+        );
+        assert_eq!(erasure_blocks.size(), 0x2080);
+        assert_eq!(
+            erasure_blocks.to_string(),
+            "Erasure block 0: 0x00100000-0x00101FFF (8 KiB)\n\
+             Erasure block 1: 0x00102000-0x0010207F (128 bytes)\n"
+        );
+    }
+
+    #[test]
+    fn recover_reconnects() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .read(&[0xE6])
+            .build();
+
+        Programmer::recover(Box::new(target.clone()))?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn programmer_states_are_send() {
+        assert_send::<Programmer>();
+        assert_send::<ProgrammerConnected>();
+        assert_send::<ProgrammerConnectedDeviceSelected>();
+        assert_send::<ProgrammerConnectedClockModeSelected>();
+        assert_send::<ProgrammerConnectedNewBitRateSelected>();
+        assert_send::<ProgrammerConnectedWaitingForIDCode>();
+        assert_send::<ProgrammerConnectedProgrammingErasureState>();
+        assert_send::<ProgrammerConnectedWaitingForData>();
+    }
+
+    #[test]
+    fn choose_multiplication_ratios_in_range() -> Result<()> {
+        use command::data::MultiplicationRatio::*;
+
+        let ratios = vec![
+            vec![DivideBy(2), MultiplyBy(1), MultiplyBy(4)],
+            vec![MultiplyBy(1), MultiplyBy(2)],
+        ];
+        let frequencies = vec![3000..=10000, 2000..=5000];
+
+        let chosen = choose_multiplication_ratios(1200.into(), &ratios, &frequencies)?;
+
+        assert_eq!(chosen, vec![MultiplyBy(4), MultiplyBy(2)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn choose_multiplication_ratios_none_in_range() {
+        use command::data::MultiplicationRatio::*;
+
+        let ratios = vec![vec![MultiplyBy(1), MultiplyBy(2)]];
+        let frequencies = vec![5000..=10000];
+
+        let result = choose_multiplication_ratios(1200.into(), &ratios, &frequencies);
+
+        assert_eq!(
+            result.err().map(|error| error.kind),
+            Some(ErrorKind::Argument)
+        );
+    }
+
+    #[test]
+    fn inquiries_can_be_repeated() -> Result<()> {
+        let supported_device_inquiry = [
+            0x30, 0x0A, 0x01, // Header
+            0x08, 0x44, 0x45, 0x56, 0x31, 0x41, 0x42, 0x43, 0x44, // Device 1
+            0xA3, // Checksum
+        ];
+        let clock_mode_inquiry = [0x31, 0x02, 0x00, 0x01, 0xCC];
+        let multiplication_ratio_inquiry = [
+            0x32, 0x04, 0x01, // Header
+            0x02, 0x01, 0x02, // Clock type 1
+            0xC4, // Checksum
+        ];
+        let operating_frequency_inquiry = [
+            0x33, 0x05, 0x01, // Header
+            0x03, 0xE8, 0x07, 0xD0, // Clock type 1
+            0x05, // Checksum
+        ];
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .read(&[0xE6])
+            .write(&[0x20])
+            .read(&supported_device_inquiry)
+            .write(&[0x20])
+            .read(&supported_device_inquiry)
+            .write(&[0x10, 0x04, 0x44, 0x45, 0x56, 0x31, 0xDC])
+            .read(&[0x06])
+            .write(&[0x21])
+            .read(&clock_mode_inquiry)
+            .write(&[0x21])
+            .read(&clock_mode_inquiry)
+            .write(&[0x11, 0x01, 0x00, 0xEE])
+            .read(&[0x06])
+            .write(&[0x22])
+            .read(&multiplication_ratio_inquiry)
+            .write(&[0x22])
+            .read(&multiplication_ratio_inquiry)
+            .write(&[0x23])
+            .read(&operating_frequency_inquiry)
+            .write(&[0x23])
+            .read(&operating_frequency_inquiry)
+            .build();
+
+        let mut prog = Programmer::new(Box::new(target.clone())).connect()?;
+        assert_eq!(prog.supported_devices()?, prog.supported_devices()?);
+
+        let mut prog = prog.select_device(&command::data::DeviceCode::new("DEV1")?)?;
+        assert_eq!(prog.clock_modes()?, prog.clock_modes()?);
+
+        let mut prog = prog.select_clock_mode(0)?;
+        assert_eq!(prog.multiplication_ratios()?, prog.multiplication_ratios()?);
+        assert_eq!(prog.operating_frequencies()?, prog.operating_frequencies()?);
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn select_device_retries_on_timeout() -> Result<()> {
+        let device_selection = [0x10, 0x04, 0x44, 0x45, 0x56, 0x31, 0xDC];
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .read(&[0xE6])
+            .write(&device_selection)
+            .write(&device_selection)
+            .read(&[0x06])
+            .build();
+        let config = ConnectConfig {
+            retry_policy: RetryPolicy {
+                max_attempts: 2,
+                ..RetryPolicy::default()
+            },
+            ..ConnectConfig::default()
+        };
+
+        let prog = Programmer::with_config(Box::new(target.clone()), config).connect()?;
+        prog.select_device(&command::data::DeviceCode::new("DEV1")?)?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn select_device_does_not_retry_rejection() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .read(&[0xE6])
+            .write(&[0x10, 0x04, 0x44, 0x45, 0x56, 0x31, 0xDC])
+            .read(&[0x90, 0x21])
+            .build();
+        let config = ConnectConfig {
+            retry_policy: RetryPolicy {
+                max_attempts: 2,
+                ..RetryPolicy::default()
+            },
+            ..ConnectConfig::default()
+        };
+
+        let prog = Programmer::with_config(Box::new(target.clone()), config).connect()?;
+        let result = prog.select_device(&command::data::DeviceCode::new("DEV1")?);
+
+        assert!(result.is_err());
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn retry_delay_saturates() {
+        let policy = RetryPolicy {
+            backoff_multiplier: 2,
+            ..RetryPolicy::default()
+        };
+
+        assert_eq!(
+            policy.next_delay(time::Duration::from_millis(100)),
+            time::Duration::from_millis(200)
+        );
+        assert_eq!(
+            policy.next_delay(time::Duration::MAX / 2 + time::Duration::from_secs(1)),
+            time::Duration::MAX
+        );
+    }
+
+    fn programming_erasure_state(
+        target: test_util::ReplayTarget,
+    ) -> ProgrammerConnectedProgrammingErasureState {
+        ProgrammerConnectedProgrammingErasureState {
+            target: Box::new(target),
+            config: ConnectConfig::default(),
+            baud_rate: 9600,
+            block_size: Some(256),
+            erasure_blocks: Some(vec![0x0..=0xFFF, 0x1000..=0x1FFF, 0x2000..=0x2FFF]),
+            user_boot_area: Some(vec![]),
+            user_area: Some(vec![0x0..=0x2FFF]),
+        }
+    }
+
+    #[test]
+    fn erase_range_erases_overlapping_blocks() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x48])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0x01, 0xA6])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0x02, 0xA5])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0xFF, 0xA8])
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        prog.erase_range(0x1000..=0x2FFF)?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn area_checksums_are_big_endian() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x4A])
+            .read(&[0x5A, 0x04, 0x12, 0x34, 0x56, 0x78, 0x8E])
+            .write(&[0x4B])
+            .read(&[0x5B, 0x04, 0x89, 0xAB, 0xCD, 0xEF, 0xB1])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        assert_eq!(prog.user_boot_area_checksum()?, 0x12345678);
+        assert_eq!(prog.user_area_checksum()?, 0x89ABCDEF);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn erase_all_blocks_erases_each_block() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x48])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0x00, 0xA7])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0x01, 0xA6])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0xFF, 0xA8])
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+        prog.erasure_blocks = Some(vec![0x0..=0xFFF, 0x1000..=0x1FFF]);
+
+        prog.erase_all_blocks()?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn erase_all_blocks_rejects_unaddressable_blocks() {
+        let target = test_util::Builder::new().build();
+        let mut prog = programming_erasure_state(target.clone());
+        prog.erasure_blocks = Some(
+            (0..=0xFFu32)
+                .map(|i| i * 0x1000..=i * 0x1000 + 0xFFF)
+                .collect(),
+        );
+
+        let result = prog.erase_all_blocks();
+
+        assert_eq!(result.map_err(|e| e.kind), Err(ErrorKind::Argument));
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn erase_block_reports_block_number_error() {
+        let target = test_util::Builder::new()
+            .write(&[0x48])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0x02, 0xA5])
+            .read(&[0xD8, 0x29])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        let result = prog.erase_block(2);
+
+        assert_eq!(result, Err(command::CommandError::BlockNumber.into()));
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn erasure_block_number_rejects_terminator() {
+        assert_eq!(erasure_block_number(0xFE), Ok(0xFE));
+        assert_eq!(
+            erasure_block_number(0xFF).map_err(|e| e.kind),
+            Err(ErrorKind::Argument)
+        );
+        assert_eq!(
+            erasure_block_number(0x100).map_err(|e| e.kind),
+            Err(ErrorKind::Argument)
+        );
+    }
+
+    #[test]
+    fn erase_range_rejects_unaligned_range() {
+        let target = test_util::Builder::new().build();
+        let mut prog = programming_erasure_state(target);
+
+        let result = prog.erase_range(0x1000..=0x1FFE);
+
+        assert_eq!(result.map_err(|e| e.kind), Err(ErrorKind::Argument));
+    }
+
+    #[test]
+    fn read_user_area_image_reads_every_region() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[
+                0x52, 0x09, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x04, 0x9F,
+            ])
+            .read(&[0x52, 0x00, 0x00, 0x00, 0x04, 0x11, 0x22, 0x33, 0x44, 0x00])
+            .write(&[
+                0x52, 0x09, 0x01, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0xA0,
+            ])
+            .read(&[0x52, 0x00, 0x00, 0x00, 0x02, 0x55, 0x66, 0xF1])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+        prog.user_area = Some(vec![0x100..=0x103, 0x200..=0x201]);
+
+        let image = prog.read_user_area_image()?;
+
+        let mut expected = Image::new(&[0x100..=0x103, 0x200..=0x201]);
+        expected.add_data(0x100, &[0x11, 0x22, 0x33, 0x44]);
+        expected.add_data(0x200, &[0x55, 0x66]);
+        assert_eq!(image, expected);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_target_gives_direct_access() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0xAA, 0x55])
+            .read(&[0x01])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        let mut response = [0u8; 1];
+        prog.with_target(|target| {
+            target.write_all(&[0xAA, 0x55])?;
+            target.read_exact(&mut response)
+        })?;
+
+        assert_eq!(response, [0x01]);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    fn select_device_confirming_status(status_reply: &[u8]) -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .read(&[0xE6])
+            .write(&[0x10, 0x04, 0x44, 0x45, 0x56, 0x31, 0xDC])
+            .read(&[0x06])
+            .write(&[0x4F])
+            .read(status_reply)
+            .build();
+        let config = ConnectConfig {
+            confirm_transitions: true,
+            ..ConnectConfig::default()
+        };
+
+        let prog = Programmer::with_config(Box::new(target.clone()), config).connect()?;
+        let result = prog.select_device(&command::data::DeviceCode::new("DEV1")?);
+
+        assert!(target.is_complete());
+
+        result.map(|_| ())
+    }
+
+    #[test]
+    fn select_device_confirms_status() {
+        assert_eq!(
+            select_device_confirming_status(&[0x5F, 0x02, 0x12, 0x00, 0x8D]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn select_device_detects_unexpected_status() {
+        assert_eq!(
+            select_device_confirming_status(&[0x5F, 0x02, 0x11, 0x00, 0x8E]).map_err(|e| e.kind),
+            Err(ErrorKind::Protocol)
+        );
+    }
+
+    #[test]
+    fn select_device_detects_desynchronised_device() {
+        assert_eq!(
+            select_device_confirming_status(&[0x06]).map_err(|e| e.kind),
+            Err(ErrorKind::Protocol)
+        );
+    }
+
+    #[test]
+    fn read_memory_rejects_out_of_bounds_range() {
+        let target = test_util::Builder::new().build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        let error = prog.read_user_memory(0x2F00, 0x200).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Argument);
+        assert_eq!(
+            error.description,
+            "512 bytes at 0x2F00 is outside the user area (0x0-0x2FFF)"
+        );
+
+        let error = prog.read_user_boot_memory(0x0, 1).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Argument);
+
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn verify_range_compares_read_bytes() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[
+                0x52, 0x09, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x04, 0x9F,
+            ])
+            .read(&[0x52, 0x00, 0x00, 0x00, 0x04, 0x11, 0x22, 0x33, 0x44, 0x00])
+            .write(&[
+                0x52, 0x09, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x04, 0x9F,
+            ])
+            .read(&[0x52, 0x00, 0x00, 0x00, 0x04, 0x11, 0x22, 0x33, 0x44, 0x00])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+        let area = command::data::MemoryArea::UserArea;
+
+        assert!(prog.verify_range(area, 0x100, &[0x11, 0x22, 0x33, 0x44])?);
+        assert!(!prog.verify_range(area, 0x100, &[0x11, 0x22, 0x33, 0x45])?);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    fn new_bit_rate_selected(
+        target: test_util::ReplayTarget,
+    ) -> ProgrammerConnectedNewBitRateSelected {
+        ProgrammerConnectedNewBitRateSelected {
+            target: Box::new(target),
+            config: ConnectConfig::default(),
+            baud_rate: 9600,
+            block_size: None,
+            erasure_blocks: None,
+            user_boot_area: None,
+            user_area: None,
+        }
+    }
+
+    #[test]
+    fn programming_erasure_state_transition_carries_inquiries() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x27])
+            .read(&[0x37, 0x02, 0x01, 0x00, 0xC6])
+            .write(&[0x25])
+            .read(&[
+                0x35, 0x09, 0x01, // Header
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0F, 0xFF, // Area 1
+                0xB3, // Checksum
+            ])
+            .write(&[0x40])
+            .read(&[0x26])
+            .build();
+        let mut prog = new_bit_rate_selected(target.clone());
+
+        prog.programming_size()?;
+        prog.user_area()?;
+        let mut prog = match prog.programming_erasure_state_transition()? {
+            ProgrammingErasureStateEntry::Entered(prog) => prog,
+            ProgrammingErasureStateEntry::IDCodeRequired(_) => panic!("expected no ID code"),
+        };
+
+        assert_eq!(prog.block_size, Some(256));
+        assert_eq!(prog.user_area, Some(vec![0x0..=0xFFF]));
+        assert_eq!(
+            prog.erase_all_blocks(),
+            Err(Error::new(
+                ErrorKind::Argument,
+                "erasure blocks unknown: call `erasure_block` before transitioning to the \
+                 programming/erasure state"
+            ))
+        );
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn programming_erasure_state_transition_inquires_programming_size() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x27])
+            .read(&[0x37, 0x02, 0x01, 0x00, 0xC6])
+            .write(&[0x40])
+            .read(&[0x26])
+            .build();
+        let prog = new_bit_rate_selected(target.clone());
+
+        let prog = match prog.programming_erasure_state_transition()? {
+            ProgrammingErasureStateEntry::Entered(prog) => prog,
+            ProgrammingErasureStateEntry::IDCodeRequired(_) => panic!("expected no ID code"),
+        };
+
+        assert_eq!(prog.block_size, Some(256));
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn programming_erasure_state_transition_submits_id_code() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x27])
+            .read(&[0x37, 0x02, 0x01, 0x00, 0xC6])
+            .write(&[0x40])
+            .read(&[0x16])
+            .write(&[
+                0x60, 0x10, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA,
+                0xAA, 0xAA, 0xAA, 0xAA, 0xF0,
+            ])
+            .read(&[0x06])
+            .build();
+        let prog = new_bit_rate_selected(target.clone());
+
+        let prog = match prog.programming_erasure_state_transition()? {
+            ProgrammingErasureStateEntry::IDCodeRequired(prog) => prog,
+            ProgrammingErasureStateEntry::Entered(_) => panic!("expected ID code to be required"),
+        };
+        prog.submit_id_code(&[0xAA; 16])?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn from_target_bypasses_handshake() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x27])
+            .read(&[0x37, 0x02, 0x01, 0x00, 0xC6])
+            .build();
+        let mut prog = ProgrammerConnectedNewBitRateSelected::from_target(
+            Box::new(target.clone()),
+            ConnectConfig::default(),
+            115200,
+        );
+
+        assert_eq!(prog.byte_rate(), 11520);
+        let (programming_size, _) =
+            prog.execute_raw(&command::commands::ProgrammingSizeInquiry {})?;
+        assert_eq!(programming_size, 256);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn current_clock_mode_confirms_selection() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .read(&[0xE6])
+            .write(&[0x10, 0x04, 0x44, 0x45, 0x56, 0x31, 0xDC])
+            .read(&[0x06])
+            .write(&[0x11, 0x01, 0x01, 0xED])
+            .read(&[0x06])
+            .write(&[0x4F])
+            .read(&[0x5F, 0x02, 0x13, 0x00, 0x8C])
+            .build();
+
+        let prog = Programmer::new(Box::new(target.clone())).connect()?;
+        let prog = prog.select_device(&command::data::DeviceCode::new("DEV1")?)?;
+        let mut prog = prog.select_clock_mode(0x01)?;
+
+        assert_eq!(prog.current_clock_mode()?, 0x01);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn current_clock_mode_rejects_wrong_state() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .read(&[0xE6])
+            .write(&[0x10, 0x04, 0x44, 0x45, 0x56, 0x31, 0xDC])
+            .read(&[0x06])
+            .write(&[0x11, 0x01, 0x01, 0xED])
+            .read(&[0x06])
+            .write(&[0x4F])
+            .read(&[0x5F, 0x02, 0x12, 0x00, 0x8D])
+            .write(&[0x4F])
+            .read(&[0xC0])
+            .build();
+
+        let prog = Programmer::new(Box::new(target.clone())).connect()?;
+        let prog = prog.select_device(&command::data::DeviceCode::new("DEV1")?)?;
+        let mut prog = prog.select_clock_mode(0x01)?;
+
+        // Status of a device still waiting for clock mode selection, then a reply which isn't
+        // a status at all
+        assert_eq!(
+            prog.current_clock_mode().map_err(|e| e.kind),
+            Err(ErrorKind::Protocol)
+        );
+        assert_eq!(
+            prog.current_clock_mode().map_err(|e| e.kind),
+            Err(ErrorKind::Protocol)
+        );
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn identify_returns_supported_devices() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .read(&[0xE6])
+            .write(&[0x20])
+            .read(&[
+                0x30, 0x0A, 0x01, // Header
+                0x08, 0x44, 0x45, 0x56, 0x31, 0x45, 0x46, 0x47, 0x48, // DEV1 EFGH
+                0x93, // Checksum
+            ])
+            .build();
+
+        let devices = identify(Box::new(target.clone()))?;
+
+        assert_eq!(
+            devices,
+            vec![command::data::SupportedDevice {
+                device_code: command::data::DeviceCode::new("DEV1")?,
+                series_name: "EFGH".to_string(),
+            }]
+        );
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn unique_supported_devices_deduplicates_by_code() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .read(&[0xE6])
+            .write(&[0x20])
+            .read(&[
+                0x30, 0x1C, 0x03, // Header
+                0x08, 0x44, 0x45, 0x56, 0x32, 0x41, 0x42, 0x43, 0x44, // DEV2 ABCD
+                0x08, 0x44, 0x45, 0x56, 0x31, 0x45, 0x46, 0x47, 0x48, // DEV1 EFGH
+                0x08, 0x44, 0x45, 0x56, 0x32, 0x61, 0x62, 0x63, 0x64, // DEV2 abcd
+                0xB9, // Checksum
+            ])
+            .build();
+
+        let mut prog = Programmer::new(Box::new(target.clone())).connect()?;
+        let devices = prog.unique_supported_devices()?;
+
+        assert_eq!(
+            devices,
+            vec![
+                command::data::SupportedDevice {
+                    device_code: command::data::DeviceCode::new("DEV1")?,
+                    series_name: "EFGH".to_string(),
+                },
+                command::data::SupportedDevice {
+                    device_code: command::data::DeviceCode::new("DEV2")?,
+                    series_name: "ABCD".to_string(),
+                },
+            ]
+        );
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn area_blank_checks_report_erasure_state() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x4C])
+            .read(&[0x06])
+            .write(&[0x4C])
+            .read(&[0xCC, 0x52])
+            .write(&[0x4D])
+            .read(&[0x06])
+            .write(&[0x4D])
+            .read(&[0xCD, 0x52])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        assert!(prog.user_boot_area_blank()?);
+        assert!(!prog.user_boot_area_blank()?);
+        assert!(prog.user_area_blank()?);
+        assert!(!prog.user_area_blank()?);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ensure_erased_erases_only_non_blank_areas() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x4C])
+            .read(&[0xCC, 0x52])
+            .write(&[0x4D])
+            .read(&[0x06])
+            .write(&[0x48])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0x02, 0xA5])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0xFF, 0xA8])
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+        prog.user_boot_area = Some(vec![0x2000..=0x2FFF]);
+        prog.user_area = Some(vec![0x0..=0x1FFF]);
+
+        assert!(prog.ensure_erased()?);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ensure_erased_rejects_non_blank_area_without_erasure_blocks() {
+        let target = test_util::Builder::new()
+            .write(&[0x4C])
+            .read(&[0xCC, 0x52])
+            .write(&[0x4D])
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+        prog.user_boot_area = Some(vec![0xFF7FC000..=0xFF7FFFFF]);
+
+        assert_eq!(
+            prog.ensure_erased().map_err(|e| e.kind),
+            Err(ErrorKind::Protocol)
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn ensure_erased_skips_blank_device() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x4C])
+            .read(&[0x06])
+            .write(&[0x4D])
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        assert!(!prog.ensure_erased()?);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_crc32_calculates_standard_crc() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[
+                0x52, 0x09, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x09, 0x9A,
+            ])
+            .read(&[
+                0x52, 0x00, 0x00, 0x00, 0x09, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39,
+                0xC8,
+            ])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        let crc = prog.read_crc32(command::data::MemoryArea::UserArea, 0x100..=0x108)?;
+
+        assert_eq!(crc, 0xCBF43926);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    // Appends the checksum the boot program expects to a command or response
+    fn with_checksum(mut bytes: Vec<u8>) -> Vec<u8> {
+        let sum = bytes.iter().fold(0u8, |sum, &x| sum.wrapping_add(x));
+        bytes.push(sum.wrapping_neg());
+        bytes
+    }
+
+    // The 256 byte programming command for `data` at `address`, padded with 0xFF
+    fn programming_command(address: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x50];
+        bytes.extend(&address.to_be_bytes());
+        bytes.extend(data);
+        bytes.resize(5 + 256, 0xFF);
+        with_checksum(bytes)
+    }
+
+    const END_PROGRAMMING: [u8; 6] = [0x50, 0xFF, 0xFF, 0xFF, 0xFF, 0xB4];
+
+    fn two_block_image() -> Image {
+        let mut image = Image::new(&[0x0..=0x2FFF]);
+        image.add_data(0x100, &[0x11, 0x22, 0x33, 0x44]);
+        image.add_data(0x300, &[0x55]);
+        image
+    }
+
+    fn block_result(address: u32, programming_error: Option<Error>) -> BlockResult {
+        BlockResult {
+            address,
+            length: 256,
+            programming_error,
+            matched: None,
+        }
+    }
+
+    #[test]
+    fn program_image_programs_populated_blocks() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x43])
+            .read(&[0x06])
+            .write(&programming_command(0x100, &[0x11, 0x22, 0x33, 0x44]))
+            .read(&[0x06])
+            .write(&programming_command(0x300, &[0x55]))
+            .read(&[0x06])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        let report = prog.program_image(&two_block_image(), VerifyMode::None)?;
+
+        assert_eq!(
+            report,
+            FlashReport {
+                verify_mode: VerifyMode::None,
+                verified: false,
+                mismatched_blocks: vec![],
+                block_results: vec![block_result(0x100, None), block_result(0x300, None)],
+                checksums: None,
+            }
+        );
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn program_image_rejects_unsupported_programming_unit() {
+        let target = test_util::Builder::new().build();
+        let mut prog = programming_erasure_state(target.clone());
+        prog.block_size = Some(128);
+
+        assert_eq!(
+            prog.program_image(&two_block_image(), VerifyMode::None)
+                .map_err(|e| e.kind),
+            Err(ErrorKind::Protocol)
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn program_image_stops_at_failing_block() {
+        let target = test_util::Builder::new()
+            .write(&[0x43])
+            .read(&[0x06])
+            .write(&programming_command(0x100, &[0x11, 0x22, 0x33, 0x44]))
+            .read(&[0xD0, 0x53])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        let result = prog.program_image(&two_block_image(), VerifyMode::None);
+
+        assert_eq!(
+            result,
+            Err(Error::new(
+                ErrorKind::Command(command::CommandError::Programming),
+                "programming failed at 0x00000100 (256 bytes): programming error"
+            ))
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn program_image_continue_on_error_records_failing_block() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x43])
+            .read(&[0x06])
+            .write(&programming_command(0x100, &[0x11, 0x22, 0x33, 0x44]))
+            .read(&[0xD0, 0x53])
+            .write(&programming_command(0x300, &[0x55]))
+            .read(&[0x06])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        let report = prog.program_image_continue_on_error(&two_block_image(), VerifyMode::None)?;
+
+        let error = Error::new(
+            ErrorKind::Command(command::CommandError::Programming),
+            "programming failed at 0x00000100 (256 bytes): programming error",
+        );
+        assert_eq!(
+            report.block_results,
+            vec![block_result(0x100, Some(error)), block_result(0x300, None)]
+        );
+        assert!(!report.verified);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    fn memory_read_command(address: u32, size: u32) -> Vec<u8> {
+        let mut bytes = vec![0x52, 0x09, 0x01];
+        bytes.extend(&address.to_be_bytes());
+        bytes.extend(&size.to_be_bytes());
+        with_checksum(bytes)
+    }
+
+    fn memory_read_response(data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x52];
+        bytes.extend(&(data.len() as u32).to_be_bytes());
+        bytes.extend(data);
+        with_checksum(bytes)
+    }
+
+    #[test]
+    fn program_and_verify_image_stops_at_mismatched_block() -> Result<()> {
+        let mut image = two_block_image();
+        image.add_data(0x500, &[0x66]);
+
+        let mut first_block = vec![0xFF; 256];
+        first_block[..4].copy_from_slice(&[0x11, 0x22, 0x33, 0x44]);
+        let target = test_util::Builder::new()
+            .write(&[0x43])
+            .read(&[0x06])
+            .write(&programming_command(0x100, &[0x11, 0x22, 0x33, 0x44]))
+            .read(&[0x06])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .write(&memory_read_command(0x100, 256))
+            .read(&memory_read_response(&first_block))
+            .write(&[0x43])
+            .read(&[0x06])
+            .write(&programming_command(0x300, &[0x55]))
+            .read(&[0x06])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .write(&memory_read_command(0x300, 256))
+            .read(&memory_read_response(&[0x00; 256]))
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        let report = prog.program_and_verify_image(&image)?;
+
+        assert_eq!(
+            report,
+            FlashReport {
+                verify_mode: VerifyMode::FullReadback,
+                verified: false,
+                mismatched_blocks: vec![0x300],
+                block_results: vec![
+                    BlockResult {
+                        matched: Some(true),
+                        ..block_result(0x100, None)
+                    },
+                    BlockResult {
+                        matched: Some(false),
+                        ..block_result(0x300, None)
+                    },
+                ],
+                checksums: None,
+            }
+        );
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn program_full_image_programs_each_area() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x42])
+            .read(&[0x06])
+            .write(&programming_command(0x3000, &[0x77]))
+            .read(&[0x06])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .write(&[0x43])
+            .read(&[0x06])
+            .write(&programming_command(0x100, &[0x11]))
+            .read(&[0x06])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+        prog.user_boot_area = Some(vec![0x3000..=0x3FFF]);
+
+        let mut image = Image::new(&[0x0..=0x3FFF]);
+        image.add_data(0x100, &[0x11]);
+        image.add_data(0x3000, &[0x77]);
+        prog.program_full_image(&image)?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn program_full_image_skips_empty_area() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x43])
+            .read(&[0x06])
+            .write(&programming_command(0x100, &[0x11]))
+            .read(&[0x06])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+        prog.user_boot_area = Some(vec![0x3000..=0x3FFF]);
+
+        let mut image = Image::new(&[0x0..=0x3FFF]);
+        image.add_data(0x100, &[0x11]);
+        prog.program_full_image(&image)?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn erase_and_program_block_skips_erased_chunks() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x48])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0x01, 0xA6])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0xFF, 0xA8])
+            .read(&[0x06])
+            .write(&[0x43])
+            .read(&[0x06])
+            .write(&programming_command(0x1000, &[0x11; 0x100]))
+            .read(&[0x06])
+            .write(&programming_command(0x1200, &[0x22; 0x10]))
+            .read(&[0x06])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        // The second chunk is entirely erased, and the last is padded to 256 bytes
+        let mut data = vec![0x11; 0x100];
+        data.extend(&[0xFF; 0x100]);
+        data.extend(&[0x22; 0x10]);
+        prog.erase_and_program_block(1, &data, 0xFF)?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn erase_and_program_block_rejects_oversized_data() {
+        let target = test_util::Builder::new().build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        let result = prog.erase_and_program_block(1, &[0x11; 0x1001], 0xFF);
+
+        assert_eq!(
+            result,
+            Err(Error::new(
+                ErrorKind::Argument,
+                "4097 bytes of data does not fit in erasure block 1 (4096 bytes)"
+            ))
+        );
+        assert!(target.is_complete());
+    }
+
+    fn waiting_for_data(target: test_util::ReplayTarget) -> ProgrammerConnectedWaitingForData {
+        ProgrammerConnectedWaitingForData {
+            target: Box::new(target),
+            config: ConnectConfig::default(),
+            baud_rate: 9600,
+            block_size: Some(256),
+            erasure_blocks: Some(vec![0x0..=0xFFF, 0x1000..=0x1FFF, 0x2000..=0x2FFF]),
+            user_boot_area: Some(vec![]),
+            user_area: Some(vec![0x0..=0x2FFF]),
+        }
+    }
+
+    #[test]
+    fn program_blocks_from_resumes_across_calls() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&programming_command(0x0, &[0x11; 0x100]))
+            .read(&[0x06])
+            .write(&programming_command(0x100, &[0x22; 0x10]))
+            .read(&[0x06])
+            .write(&programming_command(0x200, &[0x33; 0x100]))
+            .read(&[0x06])
+            .build();
+        let mut prog = waiting_for_data(target.clone());
+
+        let blocks = vec![
+            (0x0, vec![0x11; 0x100]),
+            (0x100, vec![0x22; 0x10]),
+            (0x200, vec![0x33; 0x100]),
+        ];
+        prog.program_blocks_from(blocks[..2].iter().cloned(), 0xFF)?;
+        prog.program_blocks_from(blocks[2..].iter().cloned(), 0xFF)?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn program_blocks_from_rejects_oversized_block() {
+        let target = test_util::Builder::new()
+            .write(&programming_command(0x0, &[0x11; 0x100]))
+            .read(&[0x06])
+            .build();
+        let mut prog = waiting_for_data(target.clone());
+
+        let blocks = vec![(0x0, vec![0x11; 0x100]), (0x100, vec![0x22; 0x101])];
+        let result = prog.program_blocks_from(blocks.into_iter(), 0xFF);
+
+        assert_eq!(
+            result,
+            Err(Error::new(
+                ErrorKind::Argument,
+                "block at 0x100 is 257 bytes, longer than 256 bytes"
+            ))
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn program_block_rejects_misaligned_address() {
+        let target = test_util::Builder::new().build();
+        let mut prog = waiting_for_data(target.clone());
+
+        let result = prog.program_block(0x80, [0x11; 256]);
+
+        assert_eq!(
+            result,
+            Err(Error::new(
+                ErrorKind::Argument,
+                "address 0x80 is not aligned to the 256 byte programming unit"
+            ))
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn program_block_leaves_alignment_to_device_without_block_size() {
+        let target = test_util::Builder::new()
+            .write(&programming_command(0x80, &[0x11; 0x100]))
+            .read(&[0xD0, 0x2A])
+            .build();
+        // As for a programmer created with `from_target` without a block size
+        let mut prog = waiting_for_data(target.clone());
+        prog.block_size = None;
+
+        let result = prog.program_block(0x80, [0x11; 256]);
+
+        assert_eq!(
+            result.map_err(|error| error.kind),
+            Err(ErrorKind::Command(command::CommandError::Address))
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn typed_reads_decode_both_byte_orders() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&memory_read_command(0x100, 1))
+            .read(&memory_read_response(&[0x12]))
+            .write(&memory_read_command(0x102, 2))
+            .read(&memory_read_response(&[0x12, 0x34]))
+            .write(&memory_read_command(0x102, 2))
+            .read(&memory_read_response(&[0x12, 0x34]))
+            .write(&memory_read_command(0x104, 4))
+            .read(&memory_read_response(&[0x12, 0x34, 0x56, 0x78]))
+            .write(&memory_read_command(0x104, 4))
+            .read(&memory_read_response(&[0x12, 0x34, 0x56, 0x78]))
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+        let area = command::data::MemoryArea::UserArea;
+        let (big, little) = (
+            command::data::Endianness::Big,
+            command::data::Endianness::Little,
+        );
+
+        assert_eq!(prog.read_u8(area, 0x100)?, 0x12);
+        assert_eq!(prog.read_u16(area, 0x102, big)?, 0x1234);
+        assert_eq!(prog.read_u16(area, 0x102, little)?, 0x3412);
+        assert_eq!(prog.read_u32(area, 0x104, big)?, 0x12345678);
+        assert_eq!(prog.read_u32(area, 0x104, little)?, 0x78563412);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn typed_reads_check_address() {
+        let target = test_util::Builder::new().build();
+        let mut prog = programming_erasure_state(target.clone());
+        let area = command::data::MemoryArea::UserArea;
+
+        let result = prog.read_u32(area, 0x2FFE, command::data::Endianness::Big);
+
+        assert_eq!(
+            result,
+            Err(Error::new(
+                ErrorKind::Argument,
+                "4 bytes at 0x2FFE is outside the user area (0x0-0x2FFF)"
+            ))
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn typed_reads_reject_short_response() {
+        let target = test_util::Builder::new()
+            .write(&memory_read_command(0x100, 2))
+            .read(&memory_read_response(&[0x12]))
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+        let area = command::data::MemoryArea::UserArea;
+
+        let result = prog.read_u16(area, 0x100, command::data::Endianness::Big);
+
+        assert_eq!(
+            result,
+            Err(Error::new(
+                ErrorKind::Protocol,
+                "expected 2 bytes, target returned 1"
+            ))
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn finish_requests_checksums() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x4A])
+            .read(&[0x5A, 0x04, 0x12, 0x34, 0x56, 0x78, 0x8E])
+            .write(&[0x4B])
+            .read(&[0x5B, 0x04, 0x89, 0xAB, 0xCD, 0xEF, 0xB1])
+            .build();
+        let prog = programming_erasure_state(target.clone());
+
+        let summary = prog.finish(true)?;
+
+        assert_eq!(
+            summary,
+            SessionSummary {
+                user_boot_area_checksum: Some(0x12345678),
+                user_area_checksum: Some(0x89ABCDEF),
+            }
+        );
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn finish_skips_checksums() -> Result<()> {
+        let target = test_util::Builder::new().build();
+        let prog = programming_erasure_state(target.clone());
+
+        let summary = prog.finish(false)?;
+
+        assert_eq!(
+            summary,
+            SessionSummary {
+                user_boot_area_checksum: None,
+                user_area_checksum: None,
+            }
+        );
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_resets_into_single_chip_mode() -> io::Result<()> {
+        let target = test_util::Builder::new().build();
+        let prog = programming_erasure_state(target.clone());
+
+        prog.run()?;
+
+        assert_eq!(target.resets(), vec![OperatingMode::SingleChip]);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn into_target_returns_target() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0xAA])
+            .write(&[0x55])
+            .build();
+
+        let mut raw = programming_erasure_state(target.clone()).into_target();
+        raw.write_all(&[0xAA])?;
+        let mut raw = waiting_for_data(target.clone()).into_target();
+        raw.write_all(&[0x55])?;
+
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn program_full_image_ends_programming_after_failure() {
+        let target = test_util::Builder::new()
+            .write(&[0x42])
+            .read(&[0x06])
+            .write(&programming_command(0x3000, &[0x77]))
+            .read(&[0xD0, 0x53])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+        prog.user_boot_area = Some(vec![0x3000..=0x3FFF]);
+
+        let mut image = Image::new(&[0x0..=0x3FFF]);
+        image.add_data(0x100, &[0x11]);
+        image.add_data(0x3000, &[0x77]);
+        let result = prog.program_full_image(&image);
+
+        assert_eq!(
+            result,
+            Err(Error::new(
+                ErrorKind::Command(command::CommandError::Programming),
+                "programming failed at 0x00003000 (256 bytes): programming error"
+            ))
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn erase_and_program_block_ends_programming_after_failure() {
+        let target = test_util::Builder::new()
+            .write(&[0x48])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0x01, 0xA6])
+            .read(&[0x06])
+            .write(&[0x58, 0x01, 0xFF, 0xA8])
+            .read(&[0x06])
+            .write(&[0x43])
+            .read(&[0x06])
+            .write(&programming_command(0x1000, &[0x11; 0x100]))
+            .read(&[0xD0, 0x53])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        let result = prog.erase_and_program_block(1, &[0x11; 0x200], 0xFF);
+
+        assert_eq!(
+            result.map_err(|error| error.kind),
+            Err(ErrorKind::Command(command::CommandError::Programming))
+        );
+        assert!(target.is_complete());
+    }
+
+    #[test]
+    fn program_and_verify_image_records_failing_block() -> Result<()> {
+        let target = test_util::Builder::new()
+            .write(&[0x43])
+            .read(&[0x06])
+            .write(&programming_command(0x100, &[0x11, 0x22, 0x33, 0x44]))
+            .read(&[0xD0, 0x53])
+            .write(&END_PROGRAMMING)
+            .read(&[0x06])
+            .build();
+        let mut prog = programming_erasure_state(target.clone());
+
+        let report = prog.program_and_verify_image(&two_block_image())?;
+
+        let error = Error::new(
+            ErrorKind::Command(command::CommandError::Programming),
+            "programming failed at 0x00000100 (256 bytes): programming error",
+        );
+        assert_eq!(
+            report,
+            FlashReport {
+                verify_mode: VerifyMode::FullReadback,
+                verified: false,
+                mismatched_blocks: vec![],
+                block_results: vec![block_result(0x100, Some(error))],
+                checksums: None,
+            }
+        );
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+}
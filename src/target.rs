@@ -1,6 +1,9 @@
 use std::io::{self, Read};
+use std::thread;
+use std::time;
 
 /// Chip operating modes which can be entered after a reset
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum OperatingMode {
     /// Executes main user code
     SingleChip,
@@ -12,7 +15,10 @@ pub enum OperatingMode {
 
 /// Functionality required to communicate with a target device. `io::Read` and
 /// `io::Write` traits should expose the underlying serial connection.
-pub trait Target: io::Read + io::Write {
+///
+/// Targets must be `Send`, so that a programmer (which owns its target) can be
+/// moved to another thread.
+pub trait Target: io::Read + io::Write + Send {
     /// Clears both read and write buffers of the underlying serial port
     fn clear_buffers(&mut self) -> io::Result<()>;
 
@@ -26,19 +32,112 @@ pub trait Target: io::Read + io::Write {
     /// Resets the target into the specified operating mode. Implementation
     /// unrestricted: can do anything from automatically resetting the target
     /// through the debug adapter, to asking the user to do it manually.
-    fn reset_into(&mut self, operating_mode: OperatingMode);
+    fn reset_into(&mut self, operating_mode: OperatingMode) -> io::Result<()>;
+
+    /// Returns true if `reset_into` can reset the target without user
+    /// interaction, so tools can decide up front whether to show reset
+    /// instructions
+    fn supports_auto_reset(&self) -> bool {
+        false
+    }
+
+    /// Sets how long reads wait for data before timing out. Targets without a
+    /// configurable timeout can ignore this.
+    fn set_timeout(&mut self, _timeout: time::Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns how long reads currently wait for data before timing out, or
+    /// `None` if the target has no configurable timeout
+    fn timeout(&self) -> Option<time::Duration> {
+        None
+    }
+}
+
+/// A serial port a target device may be connected to
+#[derive(Clone, Debug, PartialEq)]
+pub struct AvailableTarget {
+    /// Name of the serial port, as passed to `serialport::open`
+    pub port_name: String,
+    /// Product name reported by the adapter, if it's a USB serial adapter
+    /// which reports one
+    pub product: Option<String>,
+}
+
+/// Lists the serial ports a target device may be connected to, which may be
+/// empty if the system has no serial ports
+pub fn available_targets() -> io::Result<Vec<AvailableTarget>> {
+    let ports = serialport::available_ports()?;
+
+    Ok(ports
+        .into_iter()
+        .map(|port| AvailableTarget {
+            port_name: port.port_name,
+            product: match port.port_type {
+                serialport::SerialPortType::UsbPort(info) => info.product,
+                _ => None,
+            },
+        })
+        .collect())
+}
+
+/// Produces the text shown when the user is asked to reset the target into an
+/// operating mode
+pub type ResetPrompt = Box<dyn Fn(OperatingMode) -> String + Send>;
+
+fn default_reset_prompt(operating_mode: OperatingMode) -> String {
+    let operating_mode_str = match operating_mode {
+        OperatingMode::SingleChip => "single-chip",
+        OperatingMode::Boot => "boot",
+        OperatingMode::UserBoot => "user boot",
+    };
+
+    format!("The selected debug adapter does not support automatic reset. Please reset the target into {} mode and press ENTER.", operating_mode_str)
 }
 
 /// Implements target communication with the `serialport` crate. Prompts the
 /// user to perform manual resets.
 pub struct SerialTarget {
     p: Box<dyn serialport::SerialPort>,
+    reset_prompt: Option<ResetPrompt>,
+    continuing_message: bool,
 }
 
 impl SerialTarget {
     /// Creates a new target from the specified serial port
     pub fn new(p: Box<dyn serialport::SerialPort>) -> SerialTarget {
-        SerialTarget { p }
+        SerialTarget {
+            p,
+            reset_prompt: Some(Box::new(default_reset_prompt)),
+            continuing_message: true,
+        }
+    }
+
+    /// Sets the prompt printed to standard error before waiting for the user
+    /// to reset the target and press ENTER, or suppresses it if `None`
+    pub fn set_reset_prompt(&mut self, reset_prompt: Option<ResetPrompt>) {
+        self.reset_prompt = reset_prompt;
+    }
+
+    /// Sets whether "Continuing..." is printed to standard error once the
+    /// user has reset the target
+    pub fn set_continuing_message(&mut self, continuing_message: bool) {
+        self.continuing_message = continuing_message;
+    }
+
+    /// Sets the parity of the underlying serial port
+    pub fn set_parity(&mut self, parity: serialport::Parity) -> io::Result<()> {
+        Ok(self.p.set_parity(parity)?)
+    }
+
+    /// Sets the number of stop bits of the underlying serial port
+    pub fn set_stop_bits(&mut self, stop_bits: serialport::StopBits) -> io::Result<()> {
+        Ok(self.p.set_stop_bits(stop_bits)?)
+    }
+
+    /// Sets the number of data bits of the underlying serial port
+    pub fn set_data_bits(&mut self, data_bits: serialport::DataBits) -> io::Result<()> {
+        Ok(self.p.set_data_bits(data_bits)?)
     }
 }
 
@@ -55,18 +154,28 @@ impl Target for SerialTarget {
         Ok(self.p.bytes_to_read()?)
     }
 
-    fn reset_into(&mut self, operating_mode: OperatingMode) {
-        let operating_mode_str = match operating_mode {
-            OperatingMode::SingleChip => "single-chip",
-            OperatingMode::Boot => "boot",
-            OperatingMode::UserBoot => "user boot",
-        };
+    fn set_timeout(&mut self, timeout: time::Duration) -> io::Result<()> {
+        Ok(self.p.set_timeout(timeout)?)
+    }
+
+    fn timeout(&self) -> Option<time::Duration> {
+        Some(self.p.timeout())
+    }
+
+    fn reset_into(&mut self, operating_mode: OperatingMode) -> io::Result<()> {
+        // Prompts go to standard error, leaving standard output for the
+        // caller's results (e.g. rxprog-cli's --json output)
+        if let Some(reset_prompt) = &self.reset_prompt {
+            eprintln!("{}", reset_prompt(operating_mode));
+        }
 
-        println!("The selected debug adapter does not support automatic reset. Please reset the target into {} mode and press ENTER.", operating_mode_str);
+        io::stdin().read_exact(&mut [0u8])?;
 
-        io::stdin().read_exact(&mut [0u8]).unwrap();
+        if self.continuing_message {
+            eprintln!("Continuing...");
+        }
 
-        println!("Continuing...");
+        Ok(())
     }
 }
 
@@ -85,3 +194,513 @@ impl io::Write for SerialTarget {
         self.p.flush()
     }
 }
+
+/// Wraps another target, passing all data through unchanged while recording
+/// every byte read and written to a sink. Each line of the capture contains the
+/// time since the `TeeTarget` was created, the direction (`>` for data written
+/// to the target, `<` for data read from it) and the bytes in hex.
+pub struct TeeTarget<T: Target, W: io::Write + Send> {
+    target: T,
+    sink: W,
+    start: time::Instant,
+}
+
+impl<T: Target, W: io::Write + Send> TeeTarget<T, W> {
+    /// Creates a new target, wrapping `target` and recording traffic to `sink`
+    pub fn new(target: T, sink: W) -> TeeTarget<T, W> {
+        TeeTarget {
+            target,
+            sink,
+            start: time::Instant::now(),
+        }
+    }
+
+    /// Consumes the wrapper, returning the wrapped target and the sink
+    pub fn into_inner(self) -> (T, W) {
+        (self.target, self.sink)
+    }
+
+    fn record(&mut self, direction: char, data: &[u8]) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let elapsed = self.start.elapsed();
+        write!(
+            self.sink,
+            "[{:>4}.{:06}] {}",
+            elapsed.as_secs(),
+            elapsed.subsec_micros(),
+            direction
+        )?;
+        for byte in data {
+            write!(self.sink, " {:02X}", byte)?;
+        }
+        writeln!(self.sink)
+    }
+}
+
+impl<T: Target, W: io::Write + Send> Target for TeeTarget<T, W> {
+    fn clear_buffers(&mut self) -> io::Result<()> {
+        self.target.clear_buffers()
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> io::Result<()> {
+        self.target.set_baud_rate(baud_rate)
+    }
+
+    fn bytes_to_read(&mut self) -> io::Result<u32> {
+        self.target.bytes_to_read()
+    }
+
+    fn reset_into(&mut self, operating_mode: OperatingMode) -> io::Result<()> {
+        self.target.reset_into(operating_mode)
+    }
+
+    fn supports_auto_reset(&self) -> bool {
+        self.target.supports_auto_reset()
+    }
+
+    fn set_timeout(&mut self, timeout: time::Duration) -> io::Result<()> {
+        self.target.set_timeout(timeout)
+    }
+
+    fn timeout(&self) -> Option<time::Duration> {
+        self.target.timeout()
+    }
+}
+
+impl<T: Target, W: io::Write + Send> io::Read for TeeTarget<T, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.target.read(buf)?;
+        self.record('<', &buf[..n])?;
+
+        Ok(n)
+    }
+}
+
+impl<T: Target, W: io::Write + Send> io::Write for TeeTarget<T, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.target.write(buf)?;
+        self.record('>', &buf[..n])?;
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.target.flush()?;
+        self.sink.flush()
+    }
+}
+
+/// Wraps another target, splitting every write into chunks of at most
+/// `chunk_size` bytes with a delay between them, for serial adapters which drop
+/// bytes when sent a long burst (such as a 256 byte programming command)
+pub struct ChunkedTarget<T: Target> {
+    target: T,
+    chunk_size: usize,
+    delay: time::Duration,
+}
+
+impl<T: Target> ChunkedTarget<T> {
+    /// Creates a new target, wrapping `target`
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is zero.
+    pub fn new(target: T, chunk_size: usize, delay: time::Duration) -> ChunkedTarget<T> {
+        assert!(chunk_size > 0, "chunk size must not be zero");
+
+        ChunkedTarget {
+            target,
+            chunk_size,
+            delay,
+        }
+    }
+
+    /// Consumes the wrapper, returning the wrapped target
+    pub fn into_inner(self) -> T {
+        self.target
+    }
+}
+
+impl<T: Target> Target for ChunkedTarget<T> {
+    fn clear_buffers(&mut self) -> io::Result<()> {
+        self.target.clear_buffers()
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> io::Result<()> {
+        self.target.set_baud_rate(baud_rate)
+    }
+
+    fn bytes_to_read(&mut self) -> io::Result<u32> {
+        self.target.bytes_to_read()
+    }
+
+    fn reset_into(&mut self, operating_mode: OperatingMode) -> io::Result<()> {
+        self.target.reset_into(operating_mode)
+    }
+
+    fn supports_auto_reset(&self) -> bool {
+        self.target.supports_auto_reset()
+    }
+
+    fn set_timeout(&mut self, timeout: time::Duration) -> io::Result<()> {
+        self.target.set_timeout(timeout)
+    }
+
+    fn timeout(&self) -> Option<time::Duration> {
+        self.target.timeout()
+    }
+}
+
+impl<T: Target> io::Read for ChunkedTarget<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.target.read(buf)
+    }
+}
+
+impl<T: Target> io::Write for ChunkedTarget<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for (i, chunk) in buf.chunks(self.chunk_size).enumerate() {
+            if i != 0 {
+                thread::sleep(self.delay);
+            }
+
+            self.target.write_all(chunk)?;
+            self.target.flush()?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.target.flush()
+    }
+}
+
+/// Implements target communication over a serial port exposed through the
+/// `embedded-hal` serial traits, e.g. when programming a device from another
+/// microcontroller. Requires the `embedded-hal-target` feature.
+///
+/// `embedded-hal` has no way to change the baud rate of a serial port, so the
+/// provided `set_baud_rate` function is called to reconfigure the port.
+/// Resetting the target is left to the caller, which should reset the target
+/// into boot mode before connecting.
+#[cfg(feature = "embedded-hal-target")]
+pub struct EmbeddedHalTarget<S, F>
+where
+    S: embedded_hal::serial::Read<u8> + embedded_hal::serial::Write<u8> + Send,
+    F: FnMut(&mut S, u32) -> io::Result<()> + Send,
+{
+    serial: S,
+    set_baud_rate: F,
+    timeout: time::Duration,
+    // A byte read while checking whether data is available, which must be
+    // returned by the next read
+    pending: Option<u8>,
+}
+
+#[cfg(feature = "embedded-hal-target")]
+impl<S, F> EmbeddedHalTarget<S, F>
+where
+    S: embedded_hal::serial::Read<u8> + embedded_hal::serial::Write<u8> + Send,
+    F: FnMut(&mut S, u32) -> io::Result<()> + Send,
+{
+    /// Creates a new target from the specified serial port. Reads which
+    /// receive no data within `timeout` fail with `io::ErrorKind::TimedOut`.
+    pub fn new(serial: S, set_baud_rate: F, timeout: time::Duration) -> EmbeddedHalTarget<S, F> {
+        EmbeddedHalTarget {
+            serial,
+            set_baud_rate,
+            timeout,
+            pending: None,
+        }
+    }
+
+    /// Consumes the target, returning the serial port
+    pub fn into_inner(self) -> S {
+        self.serial
+    }
+
+    fn try_read(&mut self) -> io::Result<Option<u8>> {
+        if let Some(byte) = self.pending.take() {
+            return Ok(Some(byte));
+        }
+
+        match embedded_hal::serial::Read::read(&mut self.serial) {
+            Ok(byte) => Ok(Some(byte)),
+            Err(nb::Error::WouldBlock) => Ok(None),
+            Err(nb::Error::Other(_)) => Err(io::Error::other("serial read error")),
+        }
+    }
+}
+
+#[cfg(feature = "embedded-hal-target")]
+impl<S, F> Target for EmbeddedHalTarget<S, F>
+where
+    S: embedded_hal::serial::Read<u8> + embedded_hal::serial::Write<u8> + Send,
+    F: FnMut(&mut S, u32) -> io::Result<()> + Send,
+{
+    fn clear_buffers(&mut self) -> io::Result<()> {
+        while self.try_read()?.is_some() {}
+
+        Ok(())
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> io::Result<()> {
+        (self.set_baud_rate)(&mut self.serial, baud_rate)
+    }
+
+    fn bytes_to_read(&mut self) -> io::Result<u32> {
+        if self.pending.is_none() {
+            self.pending = self.try_read()?;
+        }
+
+        Ok(if self.pending.is_some() { 1 } else { 0 })
+    }
+
+    fn reset_into(&mut self, _operating_mode: OperatingMode) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: time::Duration) -> io::Result<()> {
+        self.timeout = timeout;
+
+        Ok(())
+    }
+
+    fn timeout(&self) -> Option<time::Duration> {
+        Some(self.timeout)
+    }
+}
+
+#[cfg(feature = "embedded-hal-target")]
+impl<S, F> io::Read for EmbeddedHalTarget<S, F>
+where
+    S: embedded_hal::serial::Read<u8> + embedded_hal::serial::Write<u8> + Send,
+    F: FnMut(&mut S, u32) -> io::Result<()> + Send,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Block until the first byte arrives, then return whatever else is
+        // immediately available
+        let start = time::Instant::now();
+        loop {
+            if let Some(byte) = self.try_read()? {
+                buf[0] = byte;
+                break;
+            }
+
+            if start.elapsed() >= self.timeout {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "serial read timed out",
+                ));
+            }
+
+            // Let other threads run while waiting, rather than spinning
+            thread::yield_now();
+        }
+
+        let mut n = 1;
+        while n < buf.len() {
+            match self.try_read()? {
+                Some(byte) => buf[n] = byte,
+                None => break,
+            }
+
+            n += 1;
+        }
+
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "embedded-hal-target")]
+impl<S, F> io::Write for EmbeddedHalTarget<S, F>
+where
+    S: embedded_hal::serial::Read<u8> + embedded_hal::serial::Write<u8> + Send,
+    F: FnMut(&mut S, u32) -> io::Result<()> + Send,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            nb::block!(embedded_hal::serial::Write::write(&mut self.serial, byte))
+                .map_err(|_| io::Error::other("serial write error"))?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        nb::block!(embedded_hal::serial::Write::flush(&mut self.serial))
+            .map_err(|_| io::Error::other("serial flush error"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    struct LoopbackTarget {
+        data: Vec<u8>,
+    }
+
+    impl Target for LoopbackTarget {
+        fn clear_buffers(&mut self) -> io::Result<()> {
+            self.data.clear();
+            Ok(())
+        }
+
+        fn set_baud_rate(&mut self, _baud_rate: u32) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn bytes_to_read(&mut self) -> io::Result<u32> {
+            Ok(self.data.len() as u32)
+        }
+
+        fn reset_into(&mut self, _operating_mode: OperatingMode) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl io::Read for LoopbackTarget {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data.drain(..n);
+
+            Ok(n)
+        }
+    }
+
+    impl io::Write for LoopbackTarget {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.data.extend_from_slice(buf);
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tee_target_records_traffic() -> io::Result<()> {
+        let mut t = TeeTarget::new(LoopbackTarget { data: vec![] }, Vec::new());
+
+        t.write_all(&[0x55, 0xAA])?;
+        let mut response = [0u8; 2];
+        t.read_exact(&mut response)?;
+
+        assert_eq!(response, [0x55, 0xAA]);
+
+        let (_, sink) = t.into_inner();
+        let capture = String::from_utf8(sink).unwrap();
+        let lines: Vec<_> = capture.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("] > 55 AA"));
+        assert!(lines[1].ends_with("] < 55 AA"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn default_reset_prompt_names_mode() {
+        let prompt = default_reset_prompt(OperatingMode::UserBoot);
+
+        assert!(prompt.contains("into user boot mode"));
+    }
+
+    #[test]
+    fn chunked_target_splits_writes() -> io::Result<()> {
+        let tee = TeeTarget::new(LoopbackTarget { data: vec![] }, Vec::new());
+        let mut t = ChunkedTarget::new(tee, 2, time::Duration::from_millis(0));
+
+        assert_eq!(t.write(&[0x01, 0x02, 0x03, 0x04, 0x05])?, 5);
+
+        let (_, sink) = t.into_inner().into_inner();
+        let capture = String::from_utf8(sink).unwrap();
+        let lines: Vec<_> = capture.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("] > 01 02"));
+        assert!(lines[1].ends_with("] > 03 04"));
+        assert!(lines[2].ends_with("] > 05"));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "embedded-hal-target")]
+    struct LoopbackSerial {
+        data: Vec<u8>,
+        baud_rate: u32,
+    }
+
+    #[cfg(feature = "embedded-hal-target")]
+    impl embedded_hal::serial::Read<u8> for LoopbackSerial {
+        type Error = ();
+
+        fn read(&mut self) -> nb::Result<u8, ()> {
+            if self.data.is_empty() {
+                Err(nb::Error::WouldBlock)
+            } else {
+                Ok(self.data.remove(0))
+            }
+        }
+    }
+
+    #[cfg(feature = "embedded-hal-target")]
+    impl embedded_hal::serial::Write<u8> for LoopbackSerial {
+        type Error = ();
+
+        fn write(&mut self, byte: u8) -> nb::Result<(), ()> {
+            self.data.push(byte);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> nb::Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-hal-target")]
+    fn embedded_hal_target_passes_data() -> io::Result<()> {
+        let serial = LoopbackSerial {
+            data: vec![],
+            baud_rate: 0,
+        };
+        let mut t = EmbeddedHalTarget::new(
+            serial,
+            |serial: &mut LoopbackSerial, baud_rate| {
+                serial.baud_rate = baud_rate;
+                Ok(())
+            },
+            time::Duration::from_millis(10),
+        );
+
+        t.set_baud_rate(9600)?;
+        assert_eq!(t.bytes_to_read()?, 0);
+
+        t.write_all(&[0x55, 0xAA])?;
+        assert_eq!(t.bytes_to_read()?, 1);
+
+        let mut response = [0u8; 2];
+        t.read_exact(&mut response)?;
+        assert_eq!(response, [0x55, 0xAA]);
+
+        let error = t.read(&mut response).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+
+        assert_eq!(t.into_inner().baud_rate, 9600);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::target::{OperatingMode, Target};
+
+#[derive(Debug)]
+enum Exchange {
+    Write(Vec<u8>),
+    Read(Vec<u8>),
+}
+
+#[derive(Debug)]
+struct Script {
+    exchanges: Vec<Exchange>,
+    // Index of the current exchange, and how many of its bytes have been
+    // consumed so far (exchanges can be split across several reads/writes)
+    exchange: usize,
+    position: usize,
+    resets: Vec<OperatingMode>,
+}
+
+impl Script {
+    fn current(&self) -> Option<&Exchange> {
+        self.exchanges.get(self.exchange)
+    }
+
+    fn advance(&mut self, length: usize, exchange_length: usize) {
+        self.position += length;
+        if self.position == exchange_length {
+            self.exchange += 1;
+            self.position = 0;
+        }
+    }
+}
+
+/// Builds a `ReplayTarget` from a recorded transcript of the bytes written to
+/// and read from a device
+pub struct Builder {
+    exchanges: Vec<Exchange>,
+}
+
+impl Builder {
+    /// Creates an empty transcript
+    pub fn new() -> Builder {
+        Builder { exchanges: vec![] }
+    }
+
+    /// Expects the programmer to write `data` to the device next
+    pub fn write(mut self, data: &[u8]) -> Builder {
+        self.exchanges.push(Exchange::Write(data.to_vec()));
+        self
+    }
+
+    /// Makes `data` available to be read by the programmer next
+    pub fn read(mut self, data: &[u8]) -> Builder {
+        self.exchanges.push(Exchange::Read(data.to_vec()));
+        self
+    }
+
+    /// Creates the target
+    pub fn build(self) -> ReplayTarget {
+        ReplayTarget {
+            script: Arc::new(Mutex::new(Script {
+                exchanges: self.exchanges,
+                exchange: 0,
+                position: 0,
+                resets: vec![],
+            })),
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
+
+/// A target which replays a recorded transcript, panicking if the programmer
+/// writes anything other than the expected bytes. Reads when the transcript
+/// expects a write time out, as a serial port would with no data to receive.
+/// Baud rate changes and buffer clears are accepted without checking, and
+/// resets are recorded for `resets` to return.
+///
+/// Clones share the same transcript, so a clone can be kept to check the
+/// transcript was completed after the original is moved into a programmer.
+///
+/// # Examples
+/// ```
+/// use rxprog::programmer::Programmer;
+/// use rxprog::test_util::Builder;
+///
+/// let target = Builder::new()
+///     .write(&[0x00])
+///     .read(&[0x00])
+///     .write(&[0x55])
+///     .read(&[0xE6])
+///     .build();
+///
+/// let prog = Programmer::new(Box::new(target.clone()));
+/// assert!(prog.connect().is_ok());
+/// assert!(target.is_complete());
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReplayTarget {
+    script: Arc<Mutex<Script>>,
+}
+
+impl ReplayTarget {
+    /// Returns true if every exchange in the transcript has been completed
+    pub fn is_complete(&self) -> bool {
+        let script = self.script.lock().unwrap();
+        script.exchange == script.exchanges.len()
+    }
+
+    /// Returns the operating modes the target has been reset into, in order
+    pub fn resets(&self) -> Vec<OperatingMode> {
+        self.script.lock().unwrap().resets.clone()
+    }
+}
+
+impl Target for ReplayTarget {
+    fn clear_buffers(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_baud_rate(&mut self, _baud_rate: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn bytes_to_read(&mut self) -> io::Result<u32> {
+        let script = self.script.lock().unwrap();
+        match script.current() {
+            Some(Exchange::Read(data)) => Ok((data.len() - script.position) as u32),
+            _ => Ok(0),
+        }
+    }
+
+    fn reset_into(&mut self, operating_mode: OperatingMode) -> io::Result<()> {
+        self.script.lock().unwrap().resets.push(operating_mode);
+        Ok(())
+    }
+}
+
+impl io::Read for ReplayTarget {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut script = self.script.lock().unwrap();
+        let position = script.position;
+        let data = match script.current() {
+            Some(Exchange::Read(data)) => data,
+            _ => return Err(io::Error::new(io::ErrorKind::TimedOut, "no data to read")),
+        };
+
+        let length = buf.len().min(data.len() - position);
+        buf[..length].copy_from_slice(&data[position..position + length]);
+
+        let exchange_length = data.len();
+        script.advance(length, exchange_length);
+
+        Ok(length)
+    }
+}
+
+impl io::Write for ReplayTarget {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut script = self.script.lock().unwrap();
+        let (exchange, position) = (script.exchange, script.position);
+        let data = match script.current() {
+            Some(Exchange::Write(data)) => data,
+            other => panic!(
+                "exchange {}: unexpected write of {:02X?}, expected {:?}",
+                exchange, buf, other
+            ),
+        };
+
+        let length = buf.len().min(data.len() - position);
+        assert_eq!(
+            &buf[..length],
+            &data[position..position + length],
+            "exchange {}: written data did not match transcript",
+            exchange
+        );
+
+        let exchange_length = data.len();
+        script.advance(length, exchange_length);
+
+        Ok(length)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::data::DeviceCode;
+    use crate::programmer::Programmer;
+
+    #[test]
+    fn replays_programmer_flow() -> crate::Result<()> {
+        let target = Builder::new()
+            .write(&[0x00])
+            .read(&[0x00])
+            .write(&[0x55])
+            .read(&[0xE6])
+            .write(&[0x10, 0x04, 0x44, 0x45, 0x56, 0x31, 0xDC])
+            .read(&[0x06])
+            .write(&[0x21])
+            .read(&[0x31, 0x02, 0x00, 0x01, 0xCC])
+            .build();
+
+        let prog = Programmer::new(Box::new(target.clone())).connect()?;
+        let mut prog = prog.select_device(&DeviceCode::new("DEV1")?)?;
+        let clock_modes = prog.clock_modes()?;
+
+        assert_eq!(clock_modes, vec![0x00, 0x01]);
+        assert!(target.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics_on_mismatched_write() {
+        let mut target = Builder::new().write(&[0x55]).build();
+
+        let _ = io::Write::write(&mut target, &[0x00]);
+    }
+}